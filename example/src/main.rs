@@ -12,6 +12,9 @@ use grammar::prime::State;
 
 fn main()
 {
+   // displays the progress of the search (use RUST_LOG=info)
+   env_logger::init();
+
    let depth = 4;
    let nb_iterations = 10_000;
    let free_memory = 900;
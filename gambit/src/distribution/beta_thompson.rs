@@ -0,0 +1,110 @@
+use super::Distribution;
+use rand::Rng;
+use rand::distributions::Beta;
+
+/// stores information gotten during previous runs
+/// NOTE: uses a Beta-Bernoulli posterior on the probability of success of the node
+/// scores are expected to be 1. for a success and 0. for a failure,
+/// intermediate scores are counted as fractional successes and other scores are clamped into [0,1]
+#[derive(Clone)]
+pub struct BetaThompson
+{
+   nb_score: u64,
+   successes: f64,
+   failures: f64
+}
+
+impl Distribution for BetaThompson
+{
+   type ScoreType = f64;
+
+   /// returns a default, empty, distribution
+   fn new() -> BetaThompson
+   {
+      BetaThompson { nb_score: 0, successes: 0., failures: 0. }
+   }
+
+   fn nb_visit(&self) -> u64
+   {
+      self.nb_score
+   }
+
+   /// adds a score to the distribution
+   /// NOTE: a NaN score is counted as a failure
+   fn update(&mut self, score: Self::ScoreType)
+   {
+      let success = if score.is_nan() { 0. } else { score.clamp(0., 1.) };
+      self.nb_score += 1;
+      self.successes += success;
+      self.failures += 1. - success;
+   }
+
+   /// gives a score to the node, we will take the node with the maximum score
+   /// the score is a sample of the posterior on the probability of success, using a uniform prior
+   fn score<RNG: Rng>(&self, _default_distribution: &BetaThompson, rng: &mut RNG) -> f64
+   {
+      rng.sample(Beta::new(1. + self.successes, 1. + self.failures))
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+
+   /// builds a distribution from a list of scores
+   fn distribution_of(scores: &[f64]) -> BetaThompson
+   {
+      let mut distribution = BetaThompson::new();
+      for &score in scores
+      {
+         distribution.update(score);
+      }
+      distribution
+   }
+
+   /// returns the mean of nb_samples scores of the distribution
+   fn mean_score(distribution: &BetaThompson, nb_samples: usize) -> f64
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let father = BetaThompson::new();
+      (0..nb_samples).map(|_| distribution.score(&father, &mut rng)).sum::<f64>() / (nb_samples as f64)
+   }
+
+   #[test]
+   fn counts_successes_and_failures()
+   {
+      let distribution = distribution_of(&[1., 0., 1., 0.5, 2., -1., std::f64::NAN]);
+      assert_eq!(distribution.nb_visit(), 7);
+      assert_eq!(distribution.successes, 3.5);
+      assert_eq!(distribution.failures, 3.5);
+   }
+
+   #[test]
+   fn samples_follow_the_posterior_mean()
+   {
+      // the posterior mean, with a uniform prior, is (1 + successes) / (2 + nb_score)
+      let nb_samples = 10_000;
+      let empty = mean_score(&BetaThompson::new(), nb_samples);
+      let successful = mean_score(&distribution_of(&[1.; 8]), nb_samples);
+      let failing = mean_score(&distribution_of(&[1., 0., 0., 0., 0., 0., 0., 0.]), nb_samples);
+      assert!((empty - 0.5).abs() < 0.02);
+      assert!((successful - 0.9).abs() < 0.02);
+      assert!((failing - 0.2).abs() < 0.02);
+   }
+
+   #[test]
+   fn samples_are_probabilities()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let father = BetaThompson::new();
+      let distribution = distribution_of(&[1., 1., 0.]);
+      for _ in 0..1000
+      {
+         let sample = distribution.score(&father, &mut rng);
+         assert!((0. ..=1.).contains(&sample));
+      }
+   }
+}
@@ -0,0 +1,208 @@
+use crate::tools::lne;
+use super::Distribution;
+use rand::Rng;
+
+/// weight kept by the previous scores each time a new score is added
+/// NOTE: a score is forgotten (weight below 1/e) after about 1/(1-DISCOUNT) updates of the node
+const DISCOUNT: f64 = 0.999;
+
+//-----------------------------------------------------------------------------
+// UCB TUNED
+
+/// UcbTuned but where the old scores are exponentially discounted
+/// useful when the statistics of a node drift during the search (pruning, no_expand mode)
+#[derive(Clone)]
+pub struct DiscountedUcbTuned
+{
+   nb_score: u64,
+   weight: f64, // discounted number of scores
+   sum_scores: f64,
+   sum_squared_score: f64
+}
+
+impl DiscountedUcbTuned
+{
+   /// returns the discounted mean score so far
+   fn mean(&self) -> f64
+   {
+      if self.nb_score == 0
+      {
+         std::f64::INFINITY
+      }
+      else
+      {
+         self.sum_scores / self.weight
+      }
+   }
+   /// returns the discounted variance of the scores so far
+   fn var(&self) -> f64
+   {
+      if self.nb_score < 2
+      {
+         0.
+      }
+      else
+      {
+         let mean = self.mean();
+         let var = self.sum_squared_score / self.weight - mean * mean;
+         var.abs() // could be negativ due to numerical unstability
+      }
+   }
+}
+
+impl Distribution for DiscountedUcbTuned
+{
+   type ScoreType = f64;
+   /// returns a default, empty, distribution
+   fn new() -> DiscountedUcbTuned
+   {
+      DiscountedUcbTuned { nb_score: 0, weight: 0., sum_scores: 0., sum_squared_score: 0. }
+   }
+
+   fn nb_visit(&self) -> u64
+   {
+      self.nb_score
+   }
+
+   /// discounts the previous scores and adds a score to the distribution
+   fn update(&mut self, score: Self::ScoreType)
+   {
+      self.nb_score += 1;
+      self.weight = DISCOUNT * self.weight + 1.;
+      self.sum_scores = DISCOUNT * self.sum_scores + score;
+      self.sum_squared_score = DISCOUNT * self.sum_squared_score + score * score;
+   }
+
+   /// gives a score to the node, we will take the node with the maximum score
+   /// NOTE: uses the discounted number of visits in the exploration term
+   fn score<RNG: Rng>(&self, default_distribution: &DiscountedUcbTuned, _rng: &mut RNG) -> f64
+   {
+      let fathers_nb_visit = default_distribution.weight;
+      let child_nb_visit = self.weight;
+      let c = self.var() + (2. * fathers_nb_visit.ln() / child_nb_visit).sqrt();
+      self.mean() + (c * fathers_nb_visit.ln() / child_nb_visit).sqrt()
+   }
+}
+
+//-----------------------------------------------------------------------------
+// THOMPSON MAX
+
+/// ThompsonMax but where the old scores are exponentially discounted
+/// the maximum decays toward the discounted mean so that an old maximum eventually stops dominating
+#[derive(Clone)]
+pub struct DiscountedThompsonMax
+{
+   nb_score: u64,
+   weight: f64, // discounted number of scores
+   sum_scores: f64,
+   max_score: f64
+}
+
+impl DiscountedThompsonMax
+{
+   /// uses the prior sample a potential score
+   fn sample<RNG: Rng>(&self, rng: &mut RNG) -> f64
+   {
+      let mean = self.sum_scores / self.weight;
+      let sup = lne(self.weight) * self.max_score;
+      mean + (sup - mean) * rng.gen::<f64>()
+   }
+}
+
+impl Distribution for DiscountedThompsonMax
+{
+   type ScoreType = f64;
+
+   /// returns a default, empty, prior
+   fn new() -> DiscountedThompsonMax
+   {
+      DiscountedThompsonMax { nb_score: 0, weight: 0., sum_scores: 0., max_score: std::f64::NEG_INFINITY }
+   }
+
+   fn nb_visit(&self) -> u64
+   {
+      self.nb_score
+   }
+
+   /// discounts the previous scores and adds a score to the prior
+   fn update(&mut self, score: Self::ScoreType)
+   {
+      self.nb_score += 1;
+      self.weight = DISCOUNT * self.weight + 1.;
+      self.sum_scores = DISCOUNT * self.sum_scores + score;
+      let mean = self.sum_scores / self.weight;
+      if self.max_score.is_finite()
+      {
+         // the maximum decays toward the mean
+         self.max_score = mean + DISCOUNT * (self.max_score - mean);
+      }
+      if score > self.max_score
+      {
+         self.max_score = score;
+      }
+   }
+
+   /// gives a score to the node, we will take the node with the maximum score
+   fn score<RNG: Rng>(&self, _default_distribution: &DiscountedThompsonMax, mut rng: &mut RNG) -> f64
+   {
+      self.sample(&mut rng)
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   /// number of updates after which the weight of a score has dropped below e^-20
+   const NB_UPDATES_TO_FORGET: usize = 20_000;
+
+   #[test]
+   fn forgets_the_oldest_scores()
+   {
+      let mut distribution = DiscountedUcbTuned::new();
+      for _ in 0..NB_UPDATES_TO_FORGET
+      {
+         distribution.update(10.);
+      }
+      for score in 0..NB_UPDATES_TO_FORGET
+      {
+         distribution.update((score % 2) as f64);
+      }
+      assert_eq!(distribution.nb_visit(), 2 * NB_UPDATES_TO_FORGET as u64);
+      assert!((distribution.mean() - 0.5).abs() < 1e-3);
+      assert!((distribution.var() - 0.25).abs() < 1e-3);
+   }
+
+   #[test]
+   fn bounds_the_discounted_number_of_scores()
+   {
+      let mut distribution = DiscountedUcbTuned::new();
+      distribution.update(1.);
+      assert_eq!(distribution.weight, 1.);
+      distribution.update(1.);
+      assert_eq!(distribution.weight, 1. + DISCOUNT);
+      for _ in 0..NB_UPDATES_TO_FORGET
+      {
+         distribution.update(1.);
+      }
+      let max_weight = 1. / (1. - DISCOUNT);
+      assert!((distribution.weight <= max_weight) && (distribution.weight > 0.99 * max_weight));
+   }
+
+   #[test]
+   fn the_maximum_decays_toward_the_mean()
+   {
+      let mut distribution = DiscountedThompsonMax::new();
+      distribution.update(100.);
+      assert_eq!(distribution.max_score, 100.);
+      for _ in 0..NB_UPDATES_TO_FORGET
+      {
+         distribution.update(1.);
+      }
+      assert!((distribution.max_score - 1.).abs() < 1e-3);
+      // a new maximum is taken immediately
+      distribution.update(2.);
+      assert_eq!(distribution.max_score, 2.);
+   }
+}
@@ -0,0 +1,167 @@
+use super::Distribution;
+use rand::Rng;
+use rand::distributions::{Gamma, Normal};
+
+/// stores information gotten during previous runs
+/// NOTE: uses a Normal-Gamma posterior on the mean and precision of the scores
+/// the prior is built from the father's distribution which makes it independent of the scale of the scores
+/// when the father cannot estimate a variance, the prior carries no information on the variance
+#[derive(Clone)]
+pub struct GaussianThompson
+{
+   nb_score: u64,
+   mean: f64,
+   sum_squared_deviations: f64 // updated with Welford's algorithm for numerical stability
+}
+
+impl GaussianThompson
+{
+   /// returns the variance of the scores so far (or None if there are not enough scores)
+   fn var(&self) -> Option<f64>
+   {
+      if self.nb_score < 2
+      {
+         None
+      }
+      else
+      {
+         Some(self.sum_squared_deviations / (self.nb_score as f64 - 1.))
+      }
+   }
+
+   /// samples a potential mean from the posterior, using the given prior (mean and variance)
+   /// a variance of None means that the prior carries no information on the variance
+   fn sample<RNG: Rng>(&self, prior_mean: f64, prior_var: Option<f64>, rng: &mut RNG) -> f64
+   {
+      // prior parameters: the prior is worth a single observation
+      let lambda_prior = 1.;
+      let alpha_prior = 1.;
+      let beta_prior = prior_var.unwrap_or(0.);
+      // posterior parameters
+      let n = self.nb_score as f64;
+      let lambda = lambda_prior + n;
+      let mean = (lambda_prior * prior_mean + n * self.mean) / lambda;
+      let alpha = alpha_prior + n / 2.;
+      let deviation = self.mean - prior_mean;
+      let beta = beta_prior
+                 + 0.5 * self.sum_squared_deviations
+                 + lambda_prior * n * deviation * deviation / (2. * lambda);
+      if beta <= 0.
+      {
+         // all the scores seen are identical, there is no uncertainty left on the mean
+         return mean;
+      }
+      // samples a precision then a mean
+      let precision = rng.sample(Gamma::new(alpha, 1. / beta)).max(std::f64::MIN_POSITIVE);
+      rng.sample(Normal::new(mean, 1. / (lambda * precision).sqrt()))
+   }
+}
+
+impl Distribution for GaussianThompson
+{
+   type ScoreType = f64;
+
+   /// returns a default, empty, distribution
+   fn new() -> GaussianThompson
+   {
+      GaussianThompson { nb_score: 0, mean: 0., sum_squared_deviations: 0. }
+   }
+
+   fn nb_visit(&self) -> u64
+   {
+      self.nb_score
+   }
+
+   /// adds a score to the distribution
+   fn update(&mut self, score: Self::ScoreType)
+   {
+      self.nb_score += 1;
+      let delta = score - self.mean;
+      self.mean += delta / (self.nb_score as f64);
+      self.sum_squared_deviations += delta * (score - self.mean);
+   }
+
+   /// gives a score to the node, we will take the node with the maximum score
+   /// the score is a sample of the posterior on the mean of the node
+   fn score<RNG: Rng>(&self, default_distribution: &GaussianThompson, rng: &mut RNG) -> f64
+   {
+      let prior_mean = default_distribution.mean;
+      let prior_var = default_distribution.var();
+      self.sample(prior_mean, prior_var, rng)
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+
+   /// builds a distribution from a list of scores
+   fn distribution_of(scores: &[f64]) -> GaussianThompson
+   {
+      let mut distribution = GaussianThompson::new();
+      for &score in scores
+      {
+         distribution.update(score);
+      }
+      distribution
+   }
+
+   #[test]
+   fn computes_mean_and_variance()
+   {
+      let distribution = distribution_of(&[1., 2., 3., 4.]);
+      assert_eq!(distribution.nb_visit(), 4);
+      assert!((distribution.mean - 2.5).abs() < 1e-12);
+      assert!((distribution.var().unwrap() - 5. / 3.).abs() < 1e-12);
+      assert!(distribution_of(&[1.]).var().is_none());
+   }
+
+   #[test]
+   fn samples_concentrate_around_the_mean()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let scores: Vec<f64> = (0..1000).map(|i| if i % 2 == 0 { 9. } else { 11. }).collect();
+      let distribution = distribution_of(&scores);
+      let father = distribution_of(&[0., 20.]);
+      for _ in 0..100
+      {
+         let sample = distribution.score(&father, &mut rng);
+         assert!((sample - 10.).abs() < 1., "sample: {}", sample);
+      }
+   }
+
+   #[test]
+   fn does_not_depend_on_the_scale_of_the_scores()
+   {
+      let scale = 1e10;
+      let scores = [-3., -1., -2.5];
+      let scaled_scores: Vec<f64> = scores.iter().map(|score| score * scale).collect();
+      // fathers with too few scores to estimate a variance
+      for father_scores in &[vec![], vec![-2.]]
+      {
+         let father = distribution_of(father_scores);
+         let scaled_father_scores: Vec<f64> = father_scores.iter().map(|score| score * scale).collect();
+         let scaled_father = distribution_of(&scaled_father_scores);
+         let mut rng = Xoshiro256Plus::seed_from_u64(0);
+         let mut scaled_rng = Xoshiro256Plus::seed_from_u64(0);
+         for _ in 0..100
+         {
+            let sample = distribution_of(&scores).score(&father, &mut rng);
+            let scaled_sample = distribution_of(&scaled_scores).score(&scaled_father, &mut scaled_rng);
+            assert!((scaled_sample / scale - sample).abs() <= 1e-6 * sample.abs());
+         }
+      }
+   }
+
+   #[test]
+   fn identical_scores_have_no_uncertainty()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let distribution = distribution_of(&[4.]);
+      let father = distribution_of(&[4.]);
+      assert_eq!(distribution.score(&father, &mut rng), 4.);
+   }
+}
@@ -1,13 +1,27 @@
 pub mod thompson_max;
 pub mod ucb_tuned;
+pub mod threshold_ascent;
+pub mod gaussian_thompson;
+pub mod beta_thompson;
+pub mod discounted;
+pub mod sliding_window;
 pub mod random;
 pub mod option;
+pub mod rave;
+pub mod timestamped;
 
 use rand::Rng;
 pub use thompson_max::ThompsonMax;
 pub use ucb_tuned::UcbTuned;
+pub use threshold_ascent::ThresholdAscent;
+pub use gaussian_thompson::GaussianThompson;
+pub use beta_thompson::BetaThompson;
+pub use discounted::{DiscountedUcbTuned, DiscountedThompsonMax};
+pub use sliding_window::{SlidingUcbTuned, SlidingThompsonMax};
 pub use random::RandomSearch;
 pub use option::Optional;
+pub use rave::Rave;
+pub use timestamped::Timestamped;
 
 pub trait Distribution: Clone
 {
@@ -24,4 +38,42 @@ pub trait Distribution: Clone
 
    /// produces a score from the distribution
    fn score<RNG: Rng>(&self, default_distribution: &Self, rng: &mut RNG) -> f64;
+
+   /// adds a score to the statistics of one of the rules of the node
+   /// called once per use of the rule, for the state of the node, in the subtree of the node
+   /// NOTE: does nothing by default (see `Rave`)
+   fn update_rule(&mut self, _rule_index: usize, _score: Self::ScoreType) {}
+
+   /// produces a score for the child associated with a rule, self being the distribution of the father
+   /// NOTE: the score of the child by default (see `Rave`)
+   fn score_child<RNG: Rng>(&self, child: &Self, _rule_index: usize, rng: &mut RNG) -> f64
+   {
+      child.score(self, rng)
+   }
+
+   /// produces a score used to order the untried rules (which are explored before any other child)
+   /// NOTE: all the untried rules are equal by default, they are then taken at random (see `Rave`)
+   fn score_untried<RNG: Rng>(&self, _rule_index: usize, _rng: &mut RNG) -> f64
+   {
+      0.
+   }
+
+   /// records that the node was visited during the given iteration of the search
+   /// NOTE: does nothing by default (see `Timestamped`)
+   fn visit(&mut self, _iteration: usize) {}
+
+   /// returns the iteration of the last visit to the node, if it is recorded
+   /// NOTE: None by default (see `Timestamped`)
+   fn last_visit(&self) -> Option<usize>
+   {
+      None
+   }
+
+   /// returns the number of bytes owned on the heap by the distribution, counted as a single allocation
+   /// NOTE: 0 by default, distributions owning heap memory must override it
+   ///       such that the memory budgets take it into account (see `TreeSize`)
+   fn heap_size(&self) -> usize
+   {
+      0
+   }
 }
@@ -8,6 +8,17 @@ pub struct Optional<Distr: Distribution>
    distribution: Distr
 }
 
+impl<Distr: Distribution> Optional<Distr>
+{
+   /// returns true if the next formula is expected to be valid
+   /// the probability is the proportion of valid formulas so far, with laplacian smoothing
+   fn sample_valid<RNG: Rng>(&self, rng: &mut RNG) -> bool
+   {
+      let nb_score = self.distribution.nb_visit();
+      rng.gen_ratio((nb_score + 1) as u32, (self.nb_visit + 2) as u32)
+   }
+}
+
 impl<UnderlyingScoreType, Distr> Distribution for Optional<Distr>
    where Distr: Distribution<ScoreType = UnderlyingScoreType>
 {
@@ -36,12 +47,107 @@ impl<UnderlyingScoreType, Distr> Distribution for Optional<Distr>
    fn score<RNG: Rng>(&self, default_distribution: &Self, rng: &mut RNG) -> f64
    {
       let nb_score = self.distribution.nb_visit();
-      let probability_valid_formula = rng.gen_ratio((nb_score + 1) as u32, (self.nb_visit + 2) as u32); // laplacian smoothing
-      match probability_valid_formula
+      match self.sample_valid(rng)
       {
          false => std::f64::NEG_INFINITY,
          true if nb_score == 0 => default_distribution.distribution.score(&default_distribution.distribution, rng),
          true => self.distribution.score(&default_distribution.distribution, rng)
       }
    }
+
+   /// adds a score to the statistics of a rule, invalid formulas are ignored (as in `update`)
+   fn update_rule(&mut self, rule_index: usize, score_opt: Self::ScoreType)
+   {
+      if let Some(score) = score_opt
+      {
+         self.distribution.update_rule(rule_index, score);
+      }
+   }
+
+   /// scores the child as in `score` but lets the underlying distribution use the statistics of the rule
+   fn score_child<RNG: Rng>(&self, child: &Self, rule_index: usize, rng: &mut RNG) -> f64
+   {
+      let nb_score = child.distribution.nb_visit();
+      match child.sample_valid(rng)
+      {
+         false => std::f64::NEG_INFINITY,
+         true if nb_score == 0 => self.distribution.score_child(&self.distribution, rule_index, rng),
+         true => self.distribution.score_child(&child.distribution, rule_index, rng)
+      }
+   }
+
+   fn score_untried<RNG: Rng>(&self, rule_index: usize, rng: &mut RNG) -> f64
+   {
+      self.distribution.score_untried(rule_index, rng)
+   }
+
+   fn visit(&mut self, iteration: usize)
+   {
+      self.distribution.visit(iteration)
+   }
+
+   fn last_visit(&self) -> Option<usize>
+   {
+      self.distribution.last_visit()
+   }
+
+   fn heap_size(&self) -> usize
+   {
+      self.distribution.heap_size()
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+   use super::super::{Rave, ThompsonMax, Timestamped};
+
+   #[test]
+   fn forwards_the_rule_statistics_to_rave()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut father = Optional::<Rave<ThompsonMax>>::new();
+      father.update(Some(1.));
+      father.update_rule(0, Some(1.));
+      father.update_rule(1, None);
+      father.update_rule(2, Some(-10.));
+      assert_eq!(father.distribution.nb_rule_visit(0), 1);
+      assert_eq!(father.distribution.nb_rule_visit(1), 0);
+      assert_eq!(father.distribution.nb_rule_visit(2), 1);
+      assert!(father.heap_size() > 0);
+      // the untried rules are ordered by amaf score
+      let unseen = father.score_untried(1, &mut rng);
+      let good = father.score_untried(0, &mut rng);
+      let bad = father.score_untried(2, &mut rng);
+      assert!((unseen > good) && (good > bad));
+   }
+
+   #[test]
+   fn blends_the_amaf_score_of_valid_children()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut father = Optional::<Rave<ThompsonMax>>::new();
+      let mut child = Optional::<Rave<ThompsonMax>>::new();
+      for _ in 0..100
+      {
+         father.update(Some(0.));
+         father.update_rule(0, Some(100.));
+         child.update(Some(0.));
+      }
+      // the child's own score is 0 but the amaf score of its rule is 100
+      assert!(father.score_child(&child, 0, &mut rng) > 50.);
+      assert_eq!(father.score_child(&child, 1, &mut rng), 0.);
+   }
+
+   #[test]
+   fn forwards_the_visits()
+   {
+      let mut distribution = Optional::<Rave<Timestamped<ThompsonMax>>>::new();
+      assert_eq!(distribution.last_visit(), Some(0));
+      distribution.visit(5);
+      assert_eq!(distribution.last_visit(), Some(5));
+   }
 }
@@ -1,9 +1,13 @@
 use super::Distribution;
 use rand::Rng;
 
-/// does not store informations
+/// does not store informations, except for its number of visits
+/// NOTE: the number of visits is used by the widening (see `ProgressiveWidening`)
 #[derive(Clone)]
-pub struct RandomSearch {}
+pub struct RandomSearch
+{
+   nb_visit: u64
+}
 
 impl Distribution for RandomSearch
 {
@@ -11,20 +15,22 @@ impl Distribution for RandomSearch
 
    fn new() -> RandomSearch
    {
-      RandomSearch {}
+      RandomSearch { nb_visit: 0 }
    }
-   
-   /// returns a dummy value
+
    fn nb_visit(&self) -> u64
    {
-      1
+      self.nb_visit
    }
 
-   fn update(&mut self, _score: Self::ScoreType) {}
+   fn update(&mut self, _score: Self::ScoreType)
+   {
+      self.nb_visit += 1;
+   }
 
    /// returns a random score
    fn score<RNG: Rng>(&self, _default_distribution: &RandomSearch, rng: &mut RNG) -> f64
    {
       rng.gen()
    }
-}
\ No newline at end of file
+}
@@ -0,0 +1,182 @@
+use super::Distribution;
+use rand::Rng;
+use std::mem::size_of;
+
+/// number of visits at which the node's own statistics and the amaf statistics have the same weight
+const RAVE_EQUIVALENCE: f64 = 1000.;
+
+/// encapsulate a distribution and adds all-moves-as-first (RAVE) statistics for each rule of the node
+/// the amaf statistics of a rule are updated by every rollout that used the rule in the subtree of the node
+/// they are blended with the score of the children early in their life, and order the untried rules
+/// NOTE: the amaf statistics are stored on the heap and grow with the rules seen (see `heap_size`)
+#[derive(Clone)]
+pub struct Rave<Distr: Distribution>
+{
+   distribution: Distr,
+   amaf: Vec<Distr> // one distribution per rule, allocated lazily
+}
+
+impl<Distr: Distribution> Rave<Distr>
+{
+   /// returns the number of rollouts that used the given rule in the subtree of the node
+   pub fn nb_rule_visit(&self, rule_index: usize) -> u64
+   {
+      self.amaf.get(rule_index).map_or(0, |amaf| amaf.nb_visit())
+   }
+
+   /// produces a score from the amaf statistics of the given rule
+   /// returns None if the rule has never been seen in the subtree
+   fn amaf_score<RNG: Rng>(&self, rule_index: usize, rng: &mut RNG) -> Option<f64>
+   {
+      match self.amaf.get(rule_index)
+      {
+         Some(amaf) if amaf.nb_visit() > 0 => Some(amaf.score(&self.distribution, rng)),
+         _ => None
+      }
+   }
+}
+
+impl<Distr: Distribution> Distribution for Rave<Distr>
+{
+   type ScoreType = Distr::ScoreType;
+
+   fn new() -> Self
+   {
+      Rave { distribution: Distr::new(), amaf: Vec::new() }
+   }
+
+   fn nb_visit(&self) -> u64
+   {
+      self.distribution.nb_visit()
+   }
+
+   fn update(&mut self, score: Self::ScoreType)
+   {
+      self.distribution.update(score)
+   }
+
+   /// returns the score of the underlying distribution
+   fn score<RNG: Rng>(&self, default_distribution: &Self, rng: &mut RNG) -> f64
+   {
+      self.distribution.score(&default_distribution.distribution, rng)
+   }
+
+   /// adds a score to the amaf statistics of the given rule
+   fn update_rule(&mut self, rule_index: usize, score: Self::ScoreType)
+   {
+      if self.amaf.len() <= rule_index
+      {
+         self.amaf.resize_with(rule_index + 1, Distr::new);
+      }
+      self.amaf[rule_index].update(score);
+   }
+
+   /// blends the child's own score with the amaf score of its rule
+   /// the amaf score dominates early in the child's life and fades as it gets visited
+   fn score_child<RNG: Rng>(&self, child: &Self, rule_index: usize, rng: &mut RNG) -> f64
+   {
+      let score = child.score(self, rng);
+      match self.amaf_score(rule_index, rng)
+      {
+         None => score,
+         Some(amaf_score) =>
+         {
+            let nb_visit = child.nb_visit() as f64;
+            let beta = (RAVE_EQUIVALENCE / (3. * nb_visit + RAVE_EQUIVALENCE)).sqrt();
+            (1. - beta) * score + beta * amaf_score
+         }
+      }
+   }
+
+   /// orders the untried rules by amaf score, the rules that have never been seen in the subtree come first
+   fn score_untried<RNG: Rng>(&self, rule_index: usize, rng: &mut RNG) -> f64
+   {
+      self.amaf_score(rule_index, rng).unwrap_or(std::f64::INFINITY)
+   }
+
+   fn visit(&mut self, iteration: usize)
+   {
+      self.distribution.visit(iteration)
+   }
+
+   fn last_visit(&self) -> Option<usize>
+   {
+      self.distribution.last_visit()
+   }
+
+   /// counts the amaf statistics, including the heap memory they own
+   fn heap_size(&self) -> usize
+   {
+      let amaf_size = self.amaf.capacity() * size_of::<Distr>();
+      let amaf_heap_size: usize = self.amaf.iter().map(|amaf| amaf.heap_size()).sum();
+      amaf_size + amaf_heap_size + self.distribution.heap_size()
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+   use super::super::{ThompsonMax, Timestamped};
+
+   #[test]
+   fn untried_rules_are_ordered_by_amaf_score()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut father = Rave::<ThompsonMax>::new();
+      father.update(1.);
+      father.update_rule(0, 1.);
+      father.update_rule(2, -10.);
+      assert_eq!(father.nb_rule_visit(0), 1);
+      assert_eq!(father.nb_rule_visit(1), 0);
+      assert_eq!(father.nb_rule_visit(2), 1);
+      let unseen = father.score_untried(1, &mut rng);
+      let good = father.score_untried(0, &mut rng);
+      let bad = father.score_untried(2, &mut rng);
+      assert!((unseen > good) && (good > bad));
+   }
+
+   #[test]
+   fn amaf_score_fades_as_the_child_is_visited()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut father = Rave::<ThompsonMax>::new();
+      let mut child = Rave::<ThompsonMax>::new();
+      for _ in 0..10
+      {
+         father.update(0.);
+         father.update_rule(0, 100.);
+         child.update(0.);
+      }
+      // the child's own score is 0 but the amaf score of its rule is 100
+      let young_score = father.score_child(&child, 0, &mut rng);
+      assert!(young_score > 50.);
+      for _ in 0..100_000
+      {
+         child.update(0.);
+      }
+      let old_score = father.score_child(&child, 0, &mut rng);
+      assert!(old_score < 10.);
+      // a rule without amaf statistics gets the child's own score
+      assert_eq!(father.score_child(&child, 1, &mut rng), child.score(&father, &mut rng));
+   }
+
+   #[test]
+   fn heap_size_grows_with_the_rules_seen()
+   {
+      let mut distribution = Rave::<ThompsonMax>::new();
+      assert_eq!(distribution.heap_size(), 0);
+      distribution.update_rule(3, 1.);
+      assert!(distribution.heap_size() >= 4 * size_of::<ThompsonMax>());
+   }
+
+   #[test]
+   fn forwards_the_visits_to_the_underlying_distribution()
+   {
+      let mut distribution = Rave::<Timestamped<ThompsonMax>>::new();
+      distribution.visit(5);
+      assert_eq!(distribution.last_visit(), Some(5));
+   }
+}
@@ -0,0 +1,232 @@
+use crate::tools::lne;
+use super::Distribution;
+use rand::Rng;
+
+/// number of recent scores kept by each node
+/// WARNING: each node stores its window inline, the memory use per node is roughly WINDOW_SIZE*8 bytes
+const WINDOW_SIZE: usize = 64;
+
+/// the latest WINDOW_SIZE scores stored in a ring buffer
+/// NOTE: the buffer is stored inline such that it is counted in the size of the nodes (see `TreeSize`)
+#[derive(Clone)]
+struct Window
+{
+   nb_score: u64,             // number of scores added since the creation of the window
+   scores: [f64; WINDOW_SIZE] // the i-th score added is stored at index i % WINDOW_SIZE
+}
+
+impl Window
+{
+   /// returns an empty window
+   fn new() -> Window
+   {
+      Window { nb_score: 0, scores: [0.; WINDOW_SIZE] }
+   }
+
+   /// returns the scores in the window, in no particular order
+   fn scores(&self) -> &[f64]
+   {
+      let len = (self.nb_score as usize).min(WINDOW_SIZE);
+      &self.scores[..len]
+   }
+
+   /// adds a score to the window, overwriting the oldest score if the window is full
+   fn push(&mut self, score: f64)
+   {
+      self.scores[(self.nb_score % WINDOW_SIZE as u64) as usize] = score;
+      self.nb_score += 1;
+   }
+}
+
+/// returns the mean of the scores
+fn mean(scores: &[f64]) -> f64
+{
+   scores.iter().sum::<f64>() / (scores.len() as f64)
+}
+
+//-----------------------------------------------------------------------------
+// UCB TUNED
+
+/// UcbTuned computed only on the latest WINDOW_SIZE scores
+/// useful when the statistics of a node drift during the search (pruning, no_expand mode)
+/// NOTE: the statistics are recomputed from the window when needed
+///       running sums would lose precision as scores leave the window
+///       and would never recover from an infinite score
+#[derive(Clone)]
+pub struct SlidingUcbTuned
+{
+   window: Window
+}
+
+impl SlidingUcbTuned
+{
+   /// returns the mean score in the window
+   fn mean(&self) -> f64
+   {
+      let scores = self.window.scores();
+      if scores.is_empty()
+      {
+         std::f64::INFINITY
+      }
+      else
+      {
+         mean(scores)
+      }
+   }
+   /// returns the (unbiased) variance of the scores in the window
+   fn var(&self) -> f64
+   {
+      let scores = self.window.scores();
+      if scores.len() < 2
+      {
+         0.
+      }
+      else
+      {
+         let mean = self.mean();
+         let sum_squared_deviations: f64 = scores.iter().map(|score| (score - mean).powi(2)).sum();
+         sum_squared_deviations / (scores.len() as f64 - 1.)
+      }
+   }
+}
+
+impl Distribution for SlidingUcbTuned
+{
+   type ScoreType = f64;
+   /// returns a default, empty, distribution
+   fn new() -> SlidingUcbTuned
+   {
+      SlidingUcbTuned { window: Window::new() }
+   }
+
+   fn nb_visit(&self) -> u64
+   {
+      self.window.nb_score
+   }
+
+   /// adds a score to the window, forgetting the oldest score if the window is full
+   fn update(&mut self, score: Self::ScoreType)
+   {
+      self.window.push(score);
+   }
+
+   /// gives a score to the node, we will take the node with the maximum score
+   /// NOTE: the exploration term uses the total number of visits, the exploitation term uses the window
+   fn score<RNG: Rng>(&self, default_distribution: &SlidingUcbTuned, _rng: &mut RNG) -> f64
+   {
+      let fathers_nb_visit = default_distribution.nb_visit() as f64;
+      let child_nb_visit = self.nb_visit() as f64;
+      let c = self.var() + (2. * fathers_nb_visit.ln() / child_nb_visit).sqrt();
+      self.mean() + (c * fathers_nb_visit.ln() / child_nb_visit).sqrt()
+   }
+}
+
+//-----------------------------------------------------------------------------
+// THOMPSON MAX
+
+/// ThompsonMax computed only on the latest WINDOW_SIZE scores
+/// NOTE: the statistics are recomputed from the window when needed (see `SlidingUcbTuned`)
+#[derive(Clone)]
+pub struct SlidingThompsonMax
+{
+   window: Window
+}
+
+impl SlidingThompsonMax
+{
+   /// uses the prior sample a potential score
+   fn sample<RNG: Rng>(&self, rng: &mut RNG) -> f64
+   {
+      let scores = self.window.scores();
+      let k = scores.len() as f64;
+      let mean = mean(scores);
+      let max_score = scores.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max);
+      let sup = lne(k) * max_score;
+      mean + (sup - mean) * rng.gen::<f64>()
+   }
+}
+
+impl Distribution for SlidingThompsonMax
+{
+   type ScoreType = f64;
+
+   /// returns a default, empty, prior
+   fn new() -> SlidingThompsonMax
+   {
+      SlidingThompsonMax { window: Window::new() }
+   }
+
+   fn nb_visit(&self) -> u64
+   {
+      self.window.nb_score
+   }
+
+   /// adds a score to the window, forgetting the oldest score if the window is full
+   fn update(&mut self, score: Self::ScoreType)
+   {
+      self.window.push(score);
+   }
+
+   /// gives a score to the node, we will take the node with the maximum score
+   fn score<RNG: Rng>(&self, _default_distribution: &SlidingThompsonMax, mut rng: &mut RNG) -> f64
+   {
+      self.sample(&mut rng)
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+
+   #[test]
+   fn forgets_the_oldest_scores()
+   {
+      let mut distribution = SlidingUcbTuned::new();
+      for _ in 0..WINDOW_SIZE
+      {
+         distribution.update(1e12);
+      }
+      for score in 0..WINDOW_SIZE
+      {
+         distribution.update((score % 2) as f64);
+      }
+      // a running sum would have lost all precision on the small scores
+      assert_eq!(distribution.nb_visit(), 2 * WINDOW_SIZE as u64);
+      assert_eq!(distribution.mean(), 0.5);
+      let expected_var = 0.25 * (WINDOW_SIZE as f64) / (WINDOW_SIZE as f64 - 1.);
+      assert!((distribution.var() - expected_var).abs() < 1e-12);
+   }
+
+   #[test]
+   fn computes_the_unbiased_variance()
+   {
+      let mut distribution = SlidingUcbTuned::new();
+      distribution.update(1.);
+      assert_eq!(distribution.var(), 0.);
+      distribution.update(3.);
+      assert_eq!(distribution.mean(), 2.);
+      assert_eq!(distribution.var(), 2.);
+   }
+
+   #[test]
+   fn recovers_from_infinite_scores()
+   {
+      let mut ucb = SlidingUcbTuned::new();
+      let mut thompson = SlidingThompsonMax::new();
+      ucb.update(std::f64::NEG_INFINITY);
+      thompson.update(std::f64::NEG_INFINITY);
+      for _ in 0..WINDOW_SIZE
+      {
+         ucb.update(2.);
+         thompson.update(2.);
+      }
+      assert_eq!(ucb.mean(), 2.);
+      assert_eq!(ucb.var(), 0.);
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let sample = thompson.sample(&mut rng);
+      assert!((sample >= 2.) && (sample <= lne(WINDOW_SIZE as f64) * 2.));
+   }
+}
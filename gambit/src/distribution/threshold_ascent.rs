@@ -0,0 +1,252 @@
+use crate::tools::lne;
+use super::Distribution;
+use rand::Rng;
+
+/// number of top scores stored per node
+/// NOTE: it is also the number of scores that are considered extreme by the father node
+const TOP_K: usize = 8;
+
+/// stores the best scores gotten during previous runs
+/// NOTE: implements the threshold ascent algorithm (Streeter & Smith, 2006) which targets the maximum score
+/// rather than the mean score, the threshold is the k-th best score seen by the father node
+#[derive(Clone)]
+pub struct ThresholdAscent
+{
+   nb_score: u64,
+   top_scores: [f64; TOP_K] // sorted in decreasing order, padded with NEG_INFINITY
+}
+
+impl ThresholdAscent
+{
+   /// returns the score above which a score is considered extreme
+   /// it is the k-th best score seen so far
+   fn threshold(&self) -> f64
+   {
+      self.top_scores[TOP_K - 1]
+   }
+
+   /// returns the number of top scores actually stored (the others being padding)
+   fn nb_top(&self) -> usize
+   {
+      (self.nb_score as usize).min(TOP_K)
+   }
+
+   /// returns the number of scores greater or equal to the given threshold
+   /// NOTE: this is exact (up to ties) when the threshold comes from an ancestor as they would be in its top-k
+   fn nb_above(&self, threshold: f64) -> usize
+   {
+      self.top_scores[..self.nb_top()].iter().take_while(|&&score| score >= threshold).count()
+   }
+}
+
+impl Distribution for ThresholdAscent
+{
+   type ScoreType = f64;
+
+   /// returns a default, empty, distribution
+   fn new() -> ThresholdAscent
+   {
+      ThresholdAscent { nb_score: 0, top_scores: [std::f64::NEG_INFINITY; TOP_K] }
+   }
+
+   fn nb_visit(&self) -> u64
+   {
+      self.nb_score
+   }
+
+   /// adds a score to the distribution, keeping only the top-k scores
+   /// NOTE: a NaN score is stored as the worst possible score
+   fn update(&mut self, score: Self::ScoreType)
+   {
+      let score = if score.is_nan() { std::f64::NEG_INFINITY } else { score };
+      let nb_top = self.nb_top();
+      self.nb_score += 1;
+      if (nb_top < TOP_K) || (score > self.threshold())
+      {
+         // inserts the score in the sorted array, dropping the smallest score if the array is full
+         let index = self.top_scores[..nb_top].iter().position(|&s| score > s).unwrap_or(nb_top);
+         for i in (index + 1..=nb_top.min(TOP_K - 1)).rev()
+         {
+            self.top_scores[i] = self.top_scores[i - 1];
+         }
+         self.top_scores[index] = score;
+      }
+   }
+
+   /// gives a score to the node, we will take the node with the maximum score
+   /// uses an upper confidence bound on the probability of beating the father's threshold
+   fn score<RNG: Rng>(&self, default_distribution: &ThresholdAscent, _rng: &mut RNG) -> f64
+   {
+      if self.nb_score == 0
+      {
+         return std::f64::INFINITY;
+      }
+      let nb_above = self.nb_above(default_distribution.threshold()) as f64;
+      let alpha = lne(default_distribution.nb_score as f64);
+      let nb_visit = self.nb_score as f64;
+      (nb_above + alpha + (2. * nb_above * alpha + alpha * alpha).sqrt()) / nb_visit
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+   use crate::grammar::{Grammar, Formula};
+   use crate::result::Single;
+   use crate::search::{search_observed, Observer, NoNormalization, NoWidening, MctsParameters};
+   use super::super::ThompsonMax;
+
+   /// number of bits in a formula
+   const NB_BITS: u8 = 10;
+
+   /// strings of bits, the first bit chooses between a safe and a risky half of the search space
+   /// the safe half always scores 0.5 while the risky half has a low mean but contains the optimum (all ones)
+   #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+   enum State
+   {
+      Bit(u8),
+      Zero,
+      One
+   }
+
+   impl Grammar for State
+   {
+      type ScoreType = f64;
+
+      fn root_state() -> Self
+      {
+         State::Bit(0)
+      }
+
+      fn expand(self) -> Vec<Vec<Self>>
+      {
+         match self
+         {
+            State::Bit(index) if index < NB_BITS =>
+            {
+               vec![vec![State::Bit(index + 1), State::Zero], vec![State::Bit(index + 1), State::One]]
+            }
+            State::Bit(_) => vec![vec![]],
+            _ => vec![]
+         }
+      }
+
+      fn to_string(formula: &Formula<Self>) -> String
+      {
+         format!("{:?}", formula.iter().collect::<Vec<_>>())
+      }
+
+      fn evaluate(formula: &Formula<Self>) -> Self::ScoreType
+      {
+         if formula[0] == State::Zero
+         {
+            return 0.5;
+         }
+         let nb_ones = formula.iter().skip(1).filter(|&&state| state == State::One).count() as f64;
+         (nb_ones / (formula.len() - 1) as f64).powi(4)
+      }
+   }
+
+   /// stops the search once the optimum has been found
+   struct StopAtOptimum
+   {
+      iteration_found: Option<usize>
+   }
+
+   impl Observer<State> for StopAtOptimum
+   {
+      fn iteration_start(&mut self, _iteration: usize) -> bool
+      {
+         self.iteration_found.is_none()
+      }
+
+      fn new_best(&mut self, iteration: usize, _formula: &Formula<State>, score: f64)
+      {
+         if score >= 1.
+         {
+            self.iteration_found = Some(iteration);
+         }
+      }
+   }
+
+   /// returns the total number of iterations needed to find the optimum over several searches
+   fn iterations_to_optimum<Distr: Distribution<ScoreType = f64>>(nb_searches: usize) -> usize
+   {
+      let nb_iterations = 1 << NB_BITS;
+      (0..nb_searches).map(|_| {
+                         let mut observer = StopAtOptimum { iteration_found: None };
+                         search_observed::<State,
+                                           Distr,
+                                           Single<State>,
+                                           NoNormalization,
+                                           NoWidening,
+                                           _>(NB_BITS as usize,
+                                              nb_iterations,
+                                              MctsParameters::default(),
+                                              &mut observer);
+                         observer.iteration_found.unwrap_or(nb_iterations)
+                      })
+                      .sum()
+   }
+
+   #[test]
+   fn keeps_top_scores_sorted()
+   {
+      let mut distribution = ThresholdAscent::new();
+      for score in &[3., 1., 4., 1., 5., 9., 2., 6., 5., 3., 5.]
+      {
+         distribution.update(*score);
+      }
+      assert_eq!(distribution.nb_visit(), 11);
+      assert_eq!(distribution.top_scores, [9., 6., 5., 5., 5., 4., 3., 3.]);
+      assert_eq!(distribution.nb_above(4.), 6);
+   }
+
+   #[test]
+   fn ignores_padding_until_full()
+   {
+      let mut distribution = ThresholdAscent::new();
+      distribution.update(std::f64::NEG_INFINITY);
+      distribution.update(2.);
+      assert_eq!(distribution.nb_above(std::f64::NEG_INFINITY), 2);
+      assert_eq!(distribution.top_scores[..2], [2., std::f64::NEG_INFINITY]);
+      distribution.update(std::f64::NAN);
+      assert_eq!(distribution.nb_above(std::f64::NEG_INFINITY), 3);
+      assert!(distribution.nb_above(0.) <= distribution.nb_visit() as usize);
+   }
+
+   #[test]
+   fn prefers_extreme_scores_over_mean()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      // a child with a good mean but no extreme score and a child with a bad mean but a rare extreme score
+      let mut safe = ThresholdAscent::new();
+      let mut risky = ThresholdAscent::new();
+      let mut father = ThresholdAscent::new();
+      for i in 0..100
+      {
+         let safe_score = 0.5;
+         let risky_score = if i % 10 == 0 { 1. } else { 0. };
+         safe.update(safe_score);
+         risky.update(risky_score);
+         father.update(safe_score);
+         father.update(risky_score);
+      }
+      assert!(risky.score(&father, &mut rng) > safe.score(&father, &mut rng));
+   }
+
+   #[test]
+   fn finds_the_optimum_faster_than_thompson_max()
+   {
+      let nb_searches = 10;
+      let threshold_ascent = iterations_to_optimum::<ThresholdAscent>(nb_searches);
+      let thompson_max = iterations_to_optimum::<ThompsonMax>(nb_searches);
+      assert!(2 * threshold_ascent < thompson_max,
+              "threshold ascent: {} iterations, thompson max: {} iterations",
+              threshold_ascent,
+              thompson_max);
+   }
+}
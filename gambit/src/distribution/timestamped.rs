@@ -0,0 +1,68 @@
+use super::Distribution;
+use rand::Rng;
+
+/// encapsulate a distribution and records the iteration of the last visit to the node
+/// used by the pruning strategies that delete the subtrees that have not been visited for the longest time
+/// NOTE: searches that never prune do not pay for the timestamp
+#[derive(Clone)]
+pub struct Timestamped<Distr: Distribution>
+{
+   distribution: Distr,
+   last_visit: usize
+}
+
+impl<Distr: Distribution> Distribution for Timestamped<Distr>
+{
+   type ScoreType = Distr::ScoreType;
+
+   fn new() -> Self
+   {
+      Timestamped { distribution: Distr::new(), last_visit: 0 }
+   }
+
+   fn nb_visit(&self) -> u64
+   {
+      self.distribution.nb_visit()
+   }
+
+   fn update(&mut self, score: Self::ScoreType)
+   {
+      self.distribution.update(score)
+   }
+
+   fn score<RNG: Rng>(&self, default_distribution: &Self, rng: &mut RNG) -> f64
+   {
+      self.distribution.score(&default_distribution.distribution, rng)
+   }
+
+   fn update_rule(&mut self, rule_index: usize, score: Self::ScoreType)
+   {
+      self.distribution.update_rule(rule_index, score)
+   }
+
+   fn score_child<RNG: Rng>(&self, child: &Self, rule_index: usize, rng: &mut RNG) -> f64
+   {
+      self.distribution.score_child(&child.distribution, rule_index, rng)
+   }
+
+   fn score_untried<RNG: Rng>(&self, rule_index: usize, rng: &mut RNG) -> f64
+   {
+      self.distribution.score_untried(rule_index, rng)
+   }
+
+   fn visit(&mut self, iteration: usize)
+   {
+      self.last_visit = iteration;
+      self.distribution.visit(iteration)
+   }
+
+   fn last_visit(&self) -> Option<usize>
+   {
+      Some(self.last_visit)
+   }
+
+   fn heap_size(&self) -> usize
+   {
+      self.distribution.heap_size()
+   }
+}
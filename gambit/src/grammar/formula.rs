@@ -2,7 +2,7 @@ use std::ops::{Deref, DerefMut};
 use super::Grammar;
 
 /// represents a serie of states
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Formula<State: Grammar>(Vec<State>);
 
 /// macro to acess methods of the inner vector
@@ -52,4 +52,10 @@ impl<State: Grammar> Formula<State>
    {
       State::cost(self)
    }
+
+   /// computes the canonical form of a formula (useful for deduplication)
+   pub fn canonical_form(&self) -> Formula<State>
+   {
+      State::canonical_form(self)
+   }
 }
@@ -15,6 +15,15 @@ pub trait Grammar
    /// an empty vector represents a terminal state: there is no rule associated with it
    fn expand(self) -> Vec<Vec<Self>>;
 
+   /// returns a prior on the rule of the given index, as returned by `expand`
+   /// when the widening limits the number of children of a node,
+   /// the rules with the highest prior are unlocked first
+   /// NOTE: rules with the same prior are unlocked in order
+   fn rule_prior(self, _rule_index: usize) -> f64
+   {
+      0.
+   }
+
    /// turn a formula into a displayable string
    fn to_string(formula: &Formula<Self>) -> String;
 
@@ -26,4 +35,18 @@ pub trait Grammar
    {
       formula.len()
    }
+
+   /// computes the objectives of the formula, all of which are maximized (useful for multi-objective fronts)
+   /// the first objective should be the score, by default the second objective is the opposite of the cost
+   fn objectives(formula: &Formula<Self>, score: f64) -> Vec<f64>
+   {
+      vec![score, -(Self::cost(formula) as f64)]
+   }
+
+   /// returns a canonical form of the formula (useful to deduplicate equivalent formulas)
+   /// two formulas are considered equivalent if they have the same canonical form
+   fn canonical_form(formula: &Formula<Self>) -> Formula<Self>
+   {
+      formula.clone()
+   }
 }
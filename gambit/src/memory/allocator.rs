@@ -0,0 +1,140 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// number of bytes currently allocated through the CountingAllocator
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+/// set to true once the CountingAllocator has been registered as the global allocator
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+/// size of the allocation used to detect whether the CountingAllocator is the global allocator
+const PROBE_SIZE: usize = 64;
+
+/// an allocator that forwards to the system allocator while counting the live bytes
+/// it is opt-in, to use it, declare it as the global allocator of your binary and register it :
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: gambit::memory::CountingAllocator = gambit::memory::CountingAllocator;
+///
+/// fn main()
+/// {
+///    assert!(gambit::memory::register_counting_allocator());
+///    ...
+/// }
+/// ```
+///
+/// NOTE: the counted bytes are the requested bytes, the bookkeeping of the system allocator is not included
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator
+{
+   unsafe fn alloc(&self, layout: Layout) -> *mut u8
+   {
+      let ptr = System.alloc(layout);
+      if !ptr.is_null()
+      {
+         LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+      }
+      ptr
+   }
+
+   unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8
+   {
+      let ptr = System.alloc_zeroed(layout);
+      if !ptr.is_null()
+      {
+         LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+      }
+      ptr
+   }
+
+   unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout)
+   {
+      System.dealloc(ptr, layout);
+      LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+   }
+
+   unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8
+   {
+      let new_ptr = System.realloc(ptr, layout, new_size);
+      if !new_ptr.is_null()
+      {
+         // on failure, the old allocation is left untouched
+         LIVE_BYTES.fetch_add(new_size, Ordering::Relaxed);
+         LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+      }
+      new_ptr
+   }
+}
+
+/// checks that the CountingAllocator is the global allocator and, if it is, enables `allocated_bytes`
+/// returns false if the CountingAllocator is not the global allocator
+/// NOTE: the check allocates through the global allocator and looks for a change in the live bytes
+pub fn register_counting_allocator() -> bool
+{
+   let live_bytes_before = LIVE_BYTES.load(Ordering::Relaxed);
+   // the probe goes through black_box such that the allocation cannot be optimized away
+   let probe = std::hint::black_box(Box::new([0u8; PROBE_SIZE]));
+   let is_installed = LIVE_BYTES.load(Ordering::Relaxed) != live_bytes_before;
+   drop(probe);
+   if is_installed
+   {
+      INSTALLED.store(true, Ordering::Relaxed);
+   }
+   is_installed
+}
+
+/// returns the number of bytes currently allocated by the program
+/// or None if the CountingAllocator has not been registered as the global allocator
+pub fn allocated_bytes() -> Option<usize>
+{
+   if INSTALLED.load(Ordering::Relaxed)
+   {
+      Some(LIVE_BYTES.load(Ordering::Relaxed))
+   }
+   else
+   {
+      None
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   #[test]
+   fn counts_live_bytes()
+   {
+      // the allocator is called directly as it is not the global allocator of the tests
+      // NOTE: this is the only test using the counters, the assertions are thus not disturbed
+      assert!(!register_counting_allocator());
+      let allocator = CountingAllocator;
+      let live_bytes = || LIVE_BYTES.load(Ordering::Relaxed);
+      let initial = live_bytes();
+      unsafe
+      {
+         let layout = Layout::from_size_align(100, 8).unwrap();
+         let ptr = allocator.alloc(layout);
+         assert!(!ptr.is_null());
+         assert_eq!(live_bytes(), initial + 100);
+         let zeroed_layout = Layout::from_size_align(50, 8).unwrap();
+         let zeroed_ptr = allocator.alloc_zeroed(zeroed_layout);
+         assert!(!zeroed_ptr.is_null());
+         assert_eq!(*zeroed_ptr.add(49), 0);
+         assert_eq!(live_bytes(), initial + 150);
+         // growing and shrinking an allocation only counts the difference
+         let ptr = allocator.realloc(ptr, layout, 300);
+         assert!(!ptr.is_null());
+         assert_eq!(live_bytes(), initial + 350);
+         let grown_layout = Layout::from_size_align(300, 8).unwrap();
+         let ptr = allocator.realloc(ptr, grown_layout, 20);
+         assert!(!ptr.is_null());
+         assert_eq!(live_bytes(), initial + 70);
+         allocator.dealloc(ptr, Layout::from_size_align(20, 8).unwrap());
+         allocator.dealloc(zeroed_ptr, zeroed_layout);
+      }
+      assert_eq!(live_bytes(), initial);
+      // using the allocator directly does not make it the global allocator
+      assert_eq!(allocated_bytes(), None);
+   }
+}
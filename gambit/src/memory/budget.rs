@@ -0,0 +1,109 @@
+use super::TreeSize;
+use super::allocator::allocated_bytes;
+
+/// a limit on the memory that the tree can use before the search stops growing it
+#[derive(Clone, Copy, Debug)]
+pub enum MemoryBudget
+{
+   /// stops growing the tree once the free RAM, in Mo, drops below the given level
+   /// NOTE: depends on the other processes running on the computer
+   FreeMemory(usize),
+   /// stops growing the tree once it contains the given number of nodes
+   Nodes(usize),
+   /// stops growing the tree once it uses the given number of bytes (as estimated by TreeSize)
+   Bytes(usize),
+   /// stops growing the tree once the program has allocated the given number of bytes
+   /// NOTE: requires the CountingAllocator to be registered as the global allocator
+   ///       falls back to the Bytes budget otherwise
+   AllocatedBytes(usize)
+}
+
+impl MemoryBudget
+{
+   /// returns true if the budget is fully used
+   /// NOTE: the FreeMemory budget is checked at the given free memory (in Mo) as it is expensive to measure
+   pub fn is_exhausted(&self, tree_size: &TreeSize, free_memory: i64) -> bool
+   {
+      match *self
+      {
+         MemoryBudget::FreeMemory(free_memory_size) => free_memory <= free_memory_size as i64,
+         MemoryBudget::Nodes(nb_nodes) => tree_size.nb_nodes >= nb_nodes,
+         MemoryBudget::Bytes(bytes) => tree_size.nb_bytes >= bytes,
+         MemoryBudget::AllocatedBytes(bytes) => allocated_bytes().unwrap_or(tree_size.nb_bytes) >= bytes
+      }
+   }
+
+   /// returns true if the budget depends on the free memory of the system
+   pub fn is_system_dependent(&self) -> bool
+   {
+      matches!(self, MemoryBudget::FreeMemory(_))
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use crate::grammar::Formula;
+   use crate::distribution::ThompsonMax;
+   use crate::result::Single;
+   use crate::search::{memory_limited_search_observed, Observer, NoNormalization, NoWidening, MctsParameters};
+   use crate::search::test_grammar::Bits;
+
+   /// records the size of the tree when the search stops growing it
+   struct RecordSwitch
+   {
+      tree_size: Option<TreeSize>,
+      nb_iterations: usize
+   }
+
+   impl Observer<Bits> for RecordSwitch
+   {
+      fn iteration_end(&mut self, _iteration: usize, _formula: &Formula<Bits>, _score: f64)
+      {
+         self.nb_iterations += 1;
+      }
+
+      fn memory_mode_switched(&mut self, _iteration: usize, tree_size: &TreeSize)
+      {
+         assert!(self.tree_size.is_none());
+         self.tree_size = Some(*tree_size);
+      }
+   }
+
+   /// runs a search with the given budget and returns the size of the tree when it stopped growing
+   fn tree_size_at_switch(budget: MemoryBudget) -> TreeSize
+   {
+      let mut observer = RecordSwitch { tree_size: None, nb_iterations: 0 };
+      memory_limited_search_observed::<Bits,
+                                       ThompsonMax,
+                                       Single<Bits>,
+                                       NoNormalization,
+                                       NoWidening,
+                                       RecordSwitch>(8,
+                                                     100,
+                                                     budget,
+                                                     MctsParameters::default(),
+                                                     &mut observer);
+      // the search goes on, without growing the tree, once the budget is used
+      assert_eq!(observer.nb_iterations, 100);
+      observer.tree_size.expect("the budget should have been used")
+   }
+
+   #[test]
+   fn nodes_budget_stops_the_growth_of_the_tree()
+   {
+      let tree_size = tree_size_at_switch(MemoryBudget::Nodes(20));
+      // the last iteration can add one node per decision
+      assert!((tree_size.nb_nodes >= 20) && (tree_size.nb_nodes < 20 + 8));
+   }
+
+   #[test]
+   fn bytes_budget_stops_the_growth_of_the_tree()
+   {
+      let tree_size = tree_size_at_switch(MemoryBudget::Bytes(4_000));
+      // the last iteration can add one node, and its children, per decision
+      let nb_bytes_per_node = tree_size.nb_bytes / tree_size.nb_nodes;
+      assert!((tree_size.nb_bytes >= 4_000) && (tree_size.nb_bytes < 4_000 + 8 * nb_bytes_per_node));
+   }
+}
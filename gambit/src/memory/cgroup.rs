@@ -0,0 +1,271 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// the memory controller of the cgroup the process lives in (as set up by docker, kubernetes, systemd, ...)
+/// it is used to respect the memory limit of a container which is invisible at the system level
+/// NOTE: the cgroup of the process is read from /proc/self/cgroup,
+///       inside a container it is usually mounted at the root of the hierarchy
+pub struct Cgroup
+{
+   limit_file: PathBuf,
+   usage_file: PathBuf,
+   stat_file: PathBuf,
+   inactive_file_key: &'static str
+}
+
+/// reads a file containing a single number of bytes
+/// returns None if the file cannot be read or does not contain a number (such as "max")
+fn read_bytes(path: &Path) -> Option<usize>
+{
+   fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// reads the value associated with a key in a file of "key value" lines
+fn read_stat(path: &Path, key: &str) -> Option<usize>
+{
+   let content = fs::read_to_string(path).ok()?;
+   content.lines()
+          .filter_map(|line| {
+             let mut words = line.split_whitespace();
+             match (words.next(), words.next())
+             {
+                (Some(k), Some(value)) if k == key => value.parse().ok(),
+                _ => None
+             }
+          })
+          .next()
+}
+
+/// parses the content of /proc/self/cgroup ("id:controllers:path" lines)
+/// returns the path of the cgroup v2 ("0::path") and the path of the cgroup v1 memory controller
+fn own_cgroups(proc_cgroup: &str) -> (Option<&str>, Option<&str>)
+{
+   let mut v2 = None;
+   let mut v1 = None;
+   for line in proc_cgroup.lines()
+   {
+      let mut fields = line.splitn(3, ':');
+      match (fields.next(), fields.next(), fields.next())
+      {
+         (Some("0"), Some(""), Some(path)) => v2 = Some(path),
+         (Some(_), Some(controllers), Some(path)) if controllers.split(',').any(|c| c == "memory") =>
+         {
+            v1 = Some(path)
+         }
+         _ => ()
+      }
+   }
+   (v2, v1)
+}
+
+/// finds the folder containing the limit file, going from the cgroup of the process up to the mount point
+/// returns the nearest folder with an actual limit or, if there is none, the nearest folder with the file
+/// NOTE: systemd often sets the limit on a parent slice rather than on the scope of the process,
+///       the path of the process does not exist below the mount point if the cgroup namespace is not private
+fn find_folder(mount: &Path, cgroup_path: &str, limit_file: &str) -> Option<PathBuf>
+{
+   let mut folder = mount.join(cgroup_path.trim_start_matches('/'));
+   let mut nearest = None;
+   loop
+   {
+      let path = folder.join(limit_file);
+      if path.is_file()
+      {
+         if read_bytes(&path).is_some()
+         {
+            return Some(folder);
+         }
+         nearest.get_or_insert_with(|| folder.clone());
+      }
+      if (folder == mount) || !folder.pop()
+      {
+         return nearest;
+      }
+   }
+}
+
+impl Cgroup
+{
+   /// detects the cgroup of the process at the usual mount point
+   /// returns None if there is no cgroup with a memory controller
+   pub fn detect() -> Option<Cgroup>
+   {
+      let proc_cgroup = fs::read_to_string("/proc/self/cgroup").unwrap_or_default();
+      Cgroup::detect_in(Path::new("/sys/fs/cgroup"), &proc_cgroup)
+   }
+
+   /// detects a cgroup v2 or v1 mounted in the given folder
+   /// `proc_cgroup` is the content of /proc/self/cgroup, the root of the hierarchy is used if it is empty
+   pub fn detect_in(root: &Path, proc_cgroup: &str) -> Option<Cgroup>
+   {
+      let (v2_path, v1_path) = own_cgroups(proc_cgroup);
+      // cgroup v2, a single hierarchy (the root cgroup has no memory.max)
+      if let Some(folder) = find_folder(root, v2_path.unwrap_or("/"), "memory.max")
+      {
+         return Some(Cgroup { limit_file: folder.join("memory.max"),
+                              usage_file: folder.join("memory.current"),
+                              stat_file: folder.join("memory.stat"),
+                              inactive_file_key: "inactive_file" });
+      }
+      // cgroup v1, one hierarchy per controller
+      if let Some(folder) = find_folder(&root.join("memory"), v1_path.unwrap_or("/"), "memory.limit_in_bytes")
+      {
+         return Some(Cgroup { limit_file: folder.join("memory.limit_in_bytes"),
+                              usage_file: folder.join("memory.usage_in_bytes"),
+                              stat_file: folder.join("memory.stat"),
+                              inactive_file_key: "total_inactive_file" });
+      }
+      None
+   }
+
+   /// returns the memory limit in bytes, None if there is no limit
+   /// NOTE: cgroup v1 expresses the absence of limit as a very large number which is returned as is
+   pub fn limit(&self) -> Option<usize>
+   {
+      read_bytes(&self.limit_file)
+   }
+
+   /// returns the memory used by the cgroup, in bytes
+   /// the inactive file cache is excluded as it can be reclaimed before the OOM killer is triggered
+   pub fn usage(&self) -> Option<usize>
+   {
+      let usage = read_bytes(&self.usage_file)?;
+      let inactive_file = read_stat(&self.stat_file, self.inactive_file_key).unwrap_or(0);
+      Some(usage.saturating_sub(inactive_file))
+   }
+
+   /// returns the memory that can still be used before reaching the limit, in bytes
+   /// returns None if there is no limit
+   pub fn free_memory(&self) -> Option<usize>
+   {
+      let limit = self.limit()?;
+      let usage = self.usage().unwrap_or(0);
+      Some(limit.saturating_sub(usage))
+   }
+}
+
+//-------------------------------------------------------------------------------------------------
+// TESTS
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   /// creates a fake cgroup folder, unique to the test, containing the given files
+   fn fake_cgroup(name: &str, files: &[(&str, &str)]) -> PathBuf
+   {
+      let root = std::env::temp_dir().join(format!("gambit_cgroup_{}_{}", name, std::process::id()));
+      let _ = fs::remove_dir_all(&root);
+      for (file, content) in files
+      {
+         let path = root.join(file);
+         fs::create_dir_all(path.parent().unwrap()).unwrap();
+         fs::write(path, content).unwrap();
+      }
+      root
+   }
+
+   #[test]
+   fn no_cgroup()
+   {
+      let root = fake_cgroup("none", &[("cpu.max", "max 100000\n")]);
+      assert!(Cgroup::detect_in(&root, "").is_none());
+      fs::remove_dir_all(root).unwrap();
+   }
+
+   #[test]
+   fn cgroup_v2()
+   {
+      let root = fake_cgroup("v2",
+                             &[("memory.max", "1000000000\n"),
+                               ("memory.current", "300000000\n"),
+                               ("memory.stat", "anon 200000000\nfile 100000000\ninactive_file 50000000\n")]);
+      let cgroup = Cgroup::detect_in(&root, "").unwrap();
+      assert_eq!(cgroup.limit(), Some(1_000_000_000));
+      assert_eq!(cgroup.usage(), Some(250_000_000));
+      assert_eq!(cgroup.free_memory(), Some(750_000_000));
+      fs::remove_dir_all(root).unwrap();
+   }
+
+   #[test]
+   fn cgroup_v2_without_limit()
+   {
+      let root = fake_cgroup("v2_max", &[("memory.max", "max\n"), ("memory.current", "300000000\n")]);
+      let cgroup = Cgroup::detect_in(&root, "").unwrap();
+      assert_eq!(cgroup.limit(), None);
+      assert_eq!(cgroup.usage(), Some(300_000_000));
+      assert_eq!(cgroup.free_memory(), None);
+      fs::remove_dir_all(root).unwrap();
+   }
+
+   #[test]
+   fn cgroup_v1()
+   {
+      let root = fake_cgroup("v1",
+                             &[("memory/memory.limit_in_bytes", "500000000\n"),
+                               ("memory/memory.usage_in_bytes", "600000000\n"),
+                               ("memory/memory.stat", "inactive_file 7\ntotal_inactive_file 50000000\n")]);
+      let cgroup = Cgroup::detect_in(&root, "").unwrap();
+      assert_eq!(cgroup.limit(), Some(500_000_000));
+      assert_eq!(cgroup.usage(), Some(550_000_000));
+      assert_eq!(cgroup.free_memory(), Some(0)); // over the limit
+      fs::remove_dir_all(root).unwrap();
+   }
+
+   #[test]
+   fn parses_proc_self_cgroup()
+   {
+      assert_eq!(own_cgroups("0::/user.slice/user-1000.slice/session-2.scope\n"),
+                 (Some("/user.slice/user-1000.slice/session-2.scope"), None));
+      assert_eq!(own_cgroups("12:cpu,cpuacct:/docker/abc\n5:memory:/docker/abc\n0::/\n"),
+                 (Some("/"), Some("/docker/abc")));
+      assert_eq!(own_cgroups(""), (None, None));
+   }
+
+   #[test]
+   fn nested_cgroup_v2()
+   {
+      let root = fake_cgroup("v2_nested",
+                             &[("memory.max", "max\n"),
+                               ("app/memory.max", "1000000000\n"),
+                               ("app/memory.current", "300000000\n"),
+                               ("other/memory.max", "2000\n")]);
+      let cgroup = Cgroup::detect_in(&root, "0::/app\n").unwrap();
+      assert_eq!(cgroup.limit(), Some(1_000_000_000));
+      assert_eq!(cgroup.usage(), Some(300_000_000));
+      fs::remove_dir_all(root).unwrap();
+   }
+
+   #[test]
+   fn limit_on_a_parent_slice()
+   {
+      // systemd sets the limit on the slice, the scope of the process has no limit
+      let root = fake_cgroup("v2_slice",
+                             &[("app.slice/memory.max", "1000000000\n"),
+                               ("app.slice/memory.current", "400000000\n"),
+                               ("app.slice/run.scope/memory.max", "max\n"),
+                               ("app.slice/run.scope/memory.current", "300000000\n")]);
+      let cgroup = Cgroup::detect_in(&root, "0::/app.slice/run.scope\n").unwrap();
+      assert_eq!(cgroup.limit(), Some(1_000_000_000));
+      assert_eq!(cgroup.usage(), Some(400_000_000));
+      // without any limit, the cgroup of the process is used
+      fs::write(root.join("app.slice/memory.max"), "max\n").unwrap();
+      let cgroup = Cgroup::detect_in(&root, "0::/app.slice/run.scope\n").unwrap();
+      assert_eq!(cgroup.limit(), None);
+      assert_eq!(cgroup.usage(), Some(300_000_000));
+      fs::remove_dir_all(root).unwrap();
+   }
+
+   #[test]
+   fn path_missing_below_the_mount_point()
+   {
+      // the cgroup namespace is not private, the cgroup of the container is mounted at the root
+      let root = fake_cgroup("v1_namespace",
+                             &[("memory/memory.limit_in_bytes", "500000000\n"),
+                               ("memory/memory.usage_in_bytes", "100000000\n")]);
+      let cgroup = Cgroup::detect_in(&root, "5:memory:/docker/abc\n0::/\n").unwrap();
+      assert_eq!(cgroup.limit(), Some(500_000_000));
+      fs::remove_dir_all(root).unwrap();
+   }
+}
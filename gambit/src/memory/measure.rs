@@ -1,52 +1,336 @@
 use crate::distribution::Distribution;
-use crate::search::{Node, Tree};
+use crate::search::{Node, Tree, Children};
 use std::mem::size_of;
+use log::{info, log_enabled, Level};
 
-/// counts the number of (tree, nodes, distr) in a tree
-fn count_elements<Distr: Distribution>(tree: &Tree<Distr>) -> (usize, usize, usize)
+/// estimated number of bytes consumed by an heap allocation of the given size
+/// including the bookkeeping and alignment of the allocator
+/// NOTE: modeled after glibc's malloc (8 bytes of header, 16 bytes alignment, 32 bytes minimum)
+fn allocation_size(size: usize) -> usize
 {
-   match tree
+   if size == 0
    {
-      Tree::Node(box Node { children, .. }) =>
+      // zero sized boxes and empty slices do not allocate
+      return 0;
+   }
+   std::cmp::max(32, (size + 8 + 15) & !15)
+}
+
+/// size of the elements of a tree
+/// can be updated incrementally as the tree grows to enforce a memory budget
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TreeSize
+{
+   /// trees stored (the untried rules of a node are not stored)
+   pub nb_trees: usize,
+   pub nb_nodes: usize,
+   /// distributions boxed in known leafs (the distributions of the nodes are stored in the nodes)
+   pub nb_distributions: usize,
+   /// number of heap allocations (one per node, up to two per set of children, one per known leaf)
+   /// plus one per distribution owning heap memory
+   pub nb_allocations: usize,
+   /// bytes owned on the heap by the distributions (see `Distribution::heap_size`)
+   pub nb_heap_bytes: usize,
+   /// bytes used by the heap allocations, including the estimated overhead of the allocator
+   pub nb_bytes: usize
+}
+
+impl TreeSize
+{
+   /// counts the elements of a tree
+   /// NOTE: the cost is proportional to the size of the tree
+   pub fn of<Distr: Distribution>(tree: &Tree<Distr>) -> TreeSize
+   {
+      match tree
+      {
+         Tree::Node(box Node { children, .. }) =>
+         {
+            let element_size = TreeSize::of_element(tree);
+            children.visited().fold(element_size, |total, (_, child)| total + TreeSize::of(child))
+         }
+         _ => TreeSize::of_element(tree)
+      }
+   }
+
+   /// counts the top element of a tree, ignoring the subtrees of its children
+   pub fn of_element<Distr: Distribution>(tree: &Tree<Distr>) -> TreeSize
+   {
+      match tree
       {
-         children.iter()
-                 .map(|child| count_elements(child))
-                 .fold((1, 1, 1), |(ta, na, da), (t, n, d)| (ta + t, na + n, da + d))
+         Tree::Node(box Node { distribution, children }) =>
+         {
+            let node_size = TreeSize { nb_trees: 1,
+                                       nb_nodes: 1,
+                                       nb_distributions: 0,
+                                       nb_allocations: 1,
+                                       nb_heap_bytes: 0,
+                                       nb_bytes: allocation_size(size_of::<Node<Distr>>()) };
+            node_size + TreeSize::of_distribution(distribution) + TreeSize::of_children(children)
+         }
+         Tree::KnownLeaf(box distribution) =>
+         {
+            let leaf_size = TreeSize { nb_trees: 1,
+                                       nb_nodes: 0,
+                                       nb_distributions: 1,
+                                       nb_allocations: 1,
+                                       nb_heap_bytes: 0,
+                                       nb_bytes: allocation_size(size_of::<Distr>()) };
+            leaf_size + TreeSize::of_distribution(distribution)
+         }
+         _ => TreeSize { nb_trees: 1, ..TreeSize::default() }
       }
-      Tree::KnownLeaf(_) => (1, 0, 1),
-      _ => (1, 0, 0)
+   }
+
+   /// counts the heap memory owned by a distribution, ignoring the distribution itself
+   /// NOTE: the heap memory of a distribution can grow without any change to the tree (see `expand`)
+   pub fn of_distribution<Distr: Distribution>(distribution: &Distr) -> TreeSize
+   {
+      let heap_size = distribution.heap_size();
+      TreeSize { nb_allocations: if heap_size > 0 { 1 } else { 0 },
+                 nb_heap_bytes: heap_size,
+                 nb_bytes: allocation_size(heap_size),
+                 ..TreeSize::default() }
+   }
+
+   /// counts the allocations used to store the children of a node, ignoring the children themselves
+   pub fn of_children<Distr: Distribution>(children: &Children<Distr>) -> TreeSize
+   {
+      let (visited_size, untried_size) = children.storage_size();
+      let nb_allocations = [visited_size, untried_size].iter().filter(|&&size| size > 0).count();
+      TreeSize { nb_allocations,
+                 nb_bytes: allocation_size(visited_size) + allocation_size(untried_size),
+                 ..TreeSize::default() }
+   }
+
+   /// updates the size when a subtree is replaced by another subtree
+   pub fn replace<Distr: Distribution>(&mut self, old_tree: &Tree<Distr>, new_tree: &Tree<Distr>)
+   {
+      *self = *self + TreeSize::of(new_tree) - TreeSize::of(old_tree);
+   }
+}
+
+impl std::ops::Add for TreeSize
+{
+   type Output = TreeSize;
+
+   fn add(self, other: TreeSize) -> TreeSize
+   {
+      TreeSize { nb_trees: self.nb_trees + other.nb_trees,
+                 nb_nodes: self.nb_nodes + other.nb_nodes,
+                 nb_distributions: self.nb_distributions + other.nb_distributions,
+                 nb_allocations: self.nb_allocations + other.nb_allocations,
+                 nb_heap_bytes: self.nb_heap_bytes + other.nb_heap_bytes,
+                 nb_bytes: self.nb_bytes + other.nb_bytes }
+   }
+}
+
+impl std::ops::Sub for TreeSize
+{
+   type Output = TreeSize;
+
+   fn sub(self, other: TreeSize) -> TreeSize
+   {
+      TreeSize { nb_trees: self.nb_trees - other.nb_trees,
+                 nb_nodes: self.nb_nodes - other.nb_nodes,
+                 nb_distributions: self.nb_distributions - other.nb_distributions,
+                 nb_allocations: self.nb_allocations - other.nb_allocations,
+                 nb_heap_bytes: self.nb_heap_bytes - other.nb_heap_bytes,
+                 nb_bytes: self.nb_bytes - other.nb_bytes }
    }
 }
 
 /// returns the full memory used, expressed in Mo
 pub fn memory_used<Distr: Distribution>(tree: &Tree<Distr>) -> usize
 {
-   let tree_size = size_of::<Tree<Distr>>();
-   let node_size = size_of::<Node<Distr>>();
-   let distr_size = size_of::<Distr>();
-   let (nb_tree, nb_nodes, nb_distr) = count_elements(tree);
-   let memory_trees = nb_tree * tree_size;
-   let memory_nodes = nb_nodes * node_size;
-   let memory_distr = nb_distr * distr_size;
-   (memory_nodes + memory_distr + memory_trees) / 1_000_000
+   TreeSize::of(tree).nb_bytes / 1_000_000
+}
+
+/// summary of the memory use of a tree
+pub struct MemoryReport
+{
+   /// trees stored (the untried rules of a node are not stored)
+   pub nb_trees: usize,
+   pub tree_size: usize,
+   pub nb_nodes: usize,
+   pub node_size: usize,
+   /// distributions boxed in known leafs
+   /// NOTE: the distributions of the nodes are not counted, they are stored in the nodes (see `node_size`)
+   pub nb_distributions: usize,
+   pub distribution_size: usize,
+   /// bytes owned on the heap by all the distributions (see `Distribution::heap_size`)
+   pub distributions_heap: usize,
+   pub nb_allocations: usize,
+   pub allocation_overhead: usize
+}
+
+impl MemoryReport
+{
+   /// returns the memory used by the trees, in bytes
+   pub fn trees_memory(&self) -> usize
+   {
+      self.nb_trees * self.tree_size
+   }
+
+   /// returns the memory used by the nodes, in bytes
+   pub fn nodes_memory(&self) -> usize
+   {
+      self.nb_nodes * self.node_size
+   }
+
+   /// returns the memory used by the distributions of the known leafs, in bytes
+   pub fn distributions_memory(&self) -> usize
+   {
+      self.nb_distributions * self.distribution_size
+   }
+
+   /// returns the full memory used, in bytes
+   pub fn total_memory(&self) -> usize
+   {
+      self.trees_memory()
+      + self.nodes_memory()
+      + self.distributions_memory()
+      + self.distributions_heap
+      + self.allocation_overhead
+   }
+}
+
+/// displays the report, in Mo
+impl std::fmt::Display for MemoryReport
+{
+   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+   {
+      writeln!(f, "Memory use: {} Mo", self.total_memory() / 1_000_000)?;
+      writeln!(f,
+               "- trees : {} Mo ({} x {} bytes)",
+               self.trees_memory() / 1_000_000,
+               self.nb_trees,
+               self.tree_size)?;
+      writeln!(f,
+               "- nodes : {} Mo ({} x {} bytes)",
+               self.nodes_memory() / 1_000_000,
+               self.nb_nodes,
+               self.node_size)?;
+      writeln!(f,
+               "- known leaf distributions : {} Mo ({} x {} bytes)",
+               self.distributions_memory() / 1_000_000,
+               self.nb_distributions,
+               self.distribution_size)?;
+      writeln!(f, "- distributions heap : {} Mo", self.distributions_heap / 1_000_000)?;
+      write!(f,
+             "- allocator overhead : {} Mo ({} allocations)",
+             self.allocation_overhead / 1_000_000,
+             self.nb_allocations)
+   }
 }
 
-/// prints a summay of the memory use of the given tree
-/// the measure seem to be optimistic by roughly 30%, maybe because of fragmentation ?
-pub fn memory_summary<Distr: Distribution>(tree: &Tree<Distr>)
+/// returns a summary of the memory use of the given tree
+/// the overhead of the allocator is estimated, use a CountingAllocator to measure the live bytes exactly
+pub fn memory_summary<Distr: Distribution>(tree: &Tree<Distr>) -> MemoryReport
 {
+   let TreeSize { nb_trees, nb_nodes, nb_distributions, nb_allocations, nb_heap_bytes, nb_bytes } =
+      TreeSize::of(tree);
    let tree_size = size_of::<Tree<Distr>>();
    let node_size = size_of::<Node<Distr>>();
-   let distr_size = size_of::<Distr>();
-
-   let (nb_tree, nb_nodes, nb_distr) = count_elements(tree);
-   let memory_trees = nb_tree * tree_size;
-   let memory_nodes = nb_nodes * node_size;
-   let memory_distr = nb_distr * distr_size;
-   let total_memory_use = memory_nodes + memory_distr + memory_trees;
-
-   println!("Memory use: {} Mo", total_memory_use / 1_000_000);
-   println!("- trees : {} Mo ({} x {} bytes)", memory_trees / 1_000_000, nb_tree, tree_size);
-   println!("- nodes : {} Mo ({} x {} bytes)", memory_nodes / 1_000_000, nb_nodes, node_size);
-   println!("- distributions : {} Mo ({} x {} bytes)", memory_distr / 1_000_000, nb_distr, distr_size);
+   let distribution_size = size_of::<Distr>();
+   let raw_bytes =
+      nb_trees * tree_size + nb_nodes * node_size + nb_distributions * distribution_size + nb_heap_bytes;
+   MemoryReport { nb_trees,
+                  tree_size,
+                  nb_nodes,
+                  node_size,
+                  nb_distributions,
+                  distribution_size,
+                  distributions_heap: nb_heap_bytes,
+                  nb_allocations,
+                  allocation_overhead: nb_bytes.saturating_sub(raw_bytes) }
+}
+
+/// logs a summary of the memory use of the given tree
+/// NOTE: the tree is only traversed if the summary will be logged
+pub fn log_memory_summary<Distr: Distribution>(tree: &Tree<Distr>)
+{
+   if !log_enabled!(target: "gambit::memory", Level::Info)
+   {
+      return;
+   }
+   let report = memory_summary(tree);
+   info!(target: "gambit::memory",
+         total_mo = report.total_memory() / 1_000_000,
+         trees = report.nb_trees,
+         tree_size = report.tree_size,
+         nodes = report.nb_nodes,
+         node_size = report.node_size,
+         distributions = report.nb_distributions,
+         distribution_size = report.distribution_size,
+         distributions_heap = report.distributions_heap,
+         allocations = report.nb_allocations,
+         allocation_overhead = report.allocation_overhead;
+         "tree memory use");
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use crate::distribution::ThompsonMax;
+   use log::{Log, Metadata, Record, LevelFilter};
+   use log::kv::{Key, Value, VisitSource, Error};
+   use std::sync::Mutex;
+
+   /// records the structured fields of the logs of the memory module
+   struct FieldsLogger
+   {
+      logs: Mutex<Vec<Vec<(String, String)>>>
+   }
+
+   /// collects the fields of a log
+   struct Fields(Vec<(String, String)>);
+
+   impl<'kvs> VisitSource<'kvs> for Fields
+   {
+      fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> std::result::Result<(), Error>
+      {
+         self.0.push((key.to_string(), value.to_string()));
+         Ok(())
+      }
+   }
+
+   impl Log for FieldsLogger
+   {
+      fn enabled(&self, metadata: &Metadata) -> bool
+      {
+         metadata.target() == "gambit::memory"
+      }
+
+      fn log(&self, record: &Record)
+      {
+         if self.enabled(record.metadata())
+         {
+            let mut fields = Fields(Vec::new());
+            record.key_values().visit(&mut fields).unwrap();
+            self.logs.lock().unwrap().push(fields.0);
+         }
+      }
+
+      fn flush(&self) {}
+   }
+
+   static LOGGER: FieldsLogger = FieldsLogger { logs: Mutex::new(Vec::new()) };
+
+   #[test]
+   fn logs_the_summary_as_structured_fields()
+   {
+      // NOTE: this is the only test installing a logger
+      log::set_logger(&LOGGER).unwrap();
+      log::set_max_level(LevelFilter::Info);
+      let tree = Tree::<ThompsonMax>::new();
+      log_memory_summary(&tree);
+      // other tests can log concurrently, we look for the summary of the empty tree
+      let logs = LOGGER.logs.lock().unwrap();
+      let tree_size = size_of::<Tree<ThompsonMax>>().to_string();
+      let expected_fields = [("trees".to_string(), "1".to_string()),
+                             ("tree_size".to_string(), tree_size),
+                             ("nodes".to_string(), "0".to_string())];
+      assert!(logs.iter().any(|fields| expected_fields.iter().all(|field| fields.contains(field))));
+   }
 }
@@ -1,5 +1,11 @@
 pub mod tracker;
 pub mod measure;
+pub mod budget;
+pub mod allocator;
+pub mod cgroup;
 
 pub use tracker::MemoryTracker;
-pub use measure::{memory_summary, memory_used};
+pub use cgroup::Cgroup;
+pub use measure::{MemoryReport, TreeSize, memory_summary, log_memory_summary, memory_used};
+pub use budget::MemoryBudget;
+pub use allocator::{CountingAllocator, allocated_bytes, register_counting_allocator};
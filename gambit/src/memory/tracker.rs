@@ -1,9 +1,13 @@
 use systemstat::{Platform, System};
+use log::info;
+use super::allocator::allocated_bytes;
+use super::cgroup::Cgroup;
 
 /// a struct to monitor memory use
 pub struct MemoryTracker
 {
    system: systemstat::platform::PlatformImpl,
+   cgroup: Option<Cgroup>,
    memory_at_creation: usize
 }
 
@@ -24,17 +28,25 @@ impl MemoryTracker
    pub fn new() -> MemoryTracker
    {
       let system = System::new();
+      let cgroup = Cgroup::detect();
       let memory_at_creation = memory_usage(&system);
-      MemoryTracker { system, memory_at_creation }
+      MemoryTracker { system, cgroup, memory_at_creation }
    }
 
    /// indicates the maount of free memory in Mo
+   /// if the process is in a cgroup with a memory limit (a container), the limit is taken into account
    pub fn free_memory(&self) -> usize
    {
-      match self.system.memory()
+      let system_free_memory = match self.system.memory()
       {
-         Ok(mem) => mem.free.as_usize() / 1_000_000,
+         Ok(mem) => mem.free.as_usize(),
          Err(x) => panic!("Unable to measure memory: {}", x)
+      };
+      let cgroup_free_memory = self.cgroup.as_ref().and_then(|cgroup| cgroup.free_memory());
+      match cgroup_free_memory
+      {
+         Some(cgroup_free_memory) => std::cmp::min(system_free_memory, cgroup_free_memory) / 1_000_000,
+         None => system_free_memory / 1_000_000
       }
    }
 
@@ -45,10 +57,22 @@ impl MemoryTracker
       current_memory - self.memory_at_creation
    }
 
-   /// displays the current memory use, in Mo
-   pub fn print_memory_usage(&self)
+   /// logs the current memory use, in Mo
+   /// includes the bytes allocated by the program if the CountingAllocator is registered as global allocator
+   pub fn log_memory_usage(&self)
    {
-      println!("memory consumption: {} Mo, free memory: {} Mo", self.memory_usage() / 1_000_000, self.free_memory());
+      match allocated_bytes()
+      {
+         Some(bytes) => info!(target: "gambit::memory",
+                              used_mo = self.memory_usage() / 1_000_000,
+                              free_mo = self.free_memory(),
+                              allocated_mo = bytes / 1_000_000;
+                              "process memory use"),
+         None => info!(target: "gambit::memory",
+                       used_mo = self.memory_usage() / 1_000_000,
+                       free_mo = self.free_memory();
+                       "process memory use")
+      }
    }
 }
 
@@ -1,8 +1,9 @@
 use super::{Result};
 use crate::grammar::{Grammar, Formula};
 use std::fmt::Display;
+use log::info;
 
-/// encapsulate a result but displays every improvement to the current best solution
+/// encapsulate a result but logs every improvement to the current best solution
 pub struct DisplayProgress<ResultType>(ResultType);
 
 /// implements the display trait needed by the result trait
@@ -31,15 +32,20 @@ impl<State, ResultType> Result<State> for DisplayProgress<ResultType>
       self.0.best()
    }
 
-   /// update the result and displays a message if we improved on the best value so far
+   /// update the result and logs a message if we improved on the best value so far
    fn update(&mut self, formula: Formula<State>, score: Self::ScoreType) -> bool
    {
       let improvement = self.0.update(formula, score);
       if improvement
       {
          let (formula, score) = self.0.best();
-         println!("New result, score={:?} for '{}'", score, formula);
+         info!(target: "gambit::result", score = score, formula:% = formula; "new result");
       }
       improvement
    }
+
+   fn search_exhausted(&mut self, nb_formulas: usize)
+   {
+      self.0.search_exhausted(nb_formulas)
+   }
 }
@@ -0,0 +1,131 @@
+use super::Result;
+use crate::grammar::{Grammar, Formula};
+use std::fmt::Display;
+
+/// encapsulate a result but records whether the search stopped because there was no formula left to explore
+/// useful to know if the best formula found is the global optimum of the search space
+pub struct Exhaustion<ResultType>
+{
+   result: ResultType,
+   nb_formulas: Option<usize> // None while the search space has not been exhausted
+}
+
+impl<ResultType> Exhaustion<ResultType>
+{
+   /// returns the number of distinct formulas evaluated if the search space has been exhausted
+   /// returns None otherwise
+   /// NOTE: see `Observer::search_exhausted` for the searches where this number is an upper bound
+   pub fn nb_formulas(&self) -> Option<usize>
+   {
+      self.nb_formulas
+   }
+
+   /// returns true if the search space has been exhausted
+   pub fn is_exhausted(&self) -> bool
+   {
+      self.nb_formulas.is_some()
+   }
+
+   /// extracts the underlying result type
+   pub fn get_result(self) -> ResultType
+   {
+      self.result
+   }
+}
+
+/// implements the display trait needed by the result trait
+impl<ResultType: Display> Display for Exhaustion<ResultType>
+{
+   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+   {
+      write!(f, "{}", self.result)
+   }
+}
+
+/// implements the result trait
+impl<State, ResultType> Result<State> for Exhaustion<ResultType>
+   where State: Grammar,
+         ResultType: Result<State>
+{
+   type ScoreType = ResultType::ScoreType;
+
+   fn new() -> Self
+   {
+      Exhaustion { result: ResultType::new(), nb_formulas: None }
+   }
+
+   fn best(&self) -> (Formula<State>, f64)
+   {
+      self.result.best()
+   }
+
+   fn update(&mut self, formula: Formula<State>, score: Self::ScoreType) -> bool
+   {
+      self.result.update(formula, score)
+   }
+
+   /// records the number of formulas evaluated
+   fn search_exhausted(&mut self, nb_formulas: usize)
+   {
+      self.nb_formulas = Some(nb_formulas);
+      self.result.search_exhausted(nb_formulas)
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use crate::distribution::UcbTuned;
+   use crate::result::Single;
+   use crate::search::{search, search_optional, exhaustive_search, count_formulas};
+   use crate::search::test_grammar::Sum;
+
+   /// the same grammar, with an optional score
+   #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+   struct OptionalState(Sum);
+
+   impl Grammar for OptionalState
+   {
+      type ScoreType = Option<f64>;
+
+      fn root_state() -> Self
+      {
+         OptionalState(Sum::root_state())
+      }
+
+      fn expand(self) -> Vec<Vec<Self>>
+      {
+         self.0.expand().into_iter().map(|rule| rule.into_iter().map(OptionalState).collect()).collect()
+      }
+
+      fn to_string(formula: &Formula<Self>) -> String
+      {
+         format!("{:?}", formula.iter().collect::<Vec<_>>())
+      }
+
+      fn evaluate(formula: &Formula<Self>) -> Self::ScoreType
+      {
+         Some(formula.len() as f64)
+      }
+   }
+
+   #[test]
+   fn records_the_exhaustion_of_the_search_space()
+   {
+      let depth = 3;
+      let nb_formulas = count_formulas::<Sum>(depth)[depth] as usize;
+      // the space is exhausted before the end of the iterations
+      let result = search::<Sum, UcbTuned, Exhaustion<Single<Sum>>>(depth, 1000);
+      assert_eq!(result.nb_formulas(), Some(nb_formulas));
+      let result = exhaustive_search::<Sum, Exhaustion<Single<Sum>>>(depth, 1000);
+      assert_eq!(result.nb_formulas(), Some(nb_formulas));
+      // the signal goes through the results encapsulating the result
+      let result = search_optional::<OptionalState, UcbTuned, Exhaustion<Single<OptionalState>>>(depth, 1000);
+      assert!(result.is_exhausted());
+      // the iterations end before the space is exhausted
+      let result = search::<Sum, UcbTuned, Exhaustion<Single<Sum>>>(depth, nb_formulas - 1);
+      assert!(!result.is_exhausted());
+      assert_eq!(result.nb_formulas(), None);
+   }
+}
@@ -0,0 +1,330 @@
+use super::Result;
+use crate::grammar::{Grammar, Formula};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+//-------------------------------------------------------------------------------------------------
+// TYPES
+
+/// represents a recorded evaluation
+pub struct Record
+{
+   pub iteration: usize,
+   pub elapsed_seconds: f64,
+   pub score: Option<f64>, // None if the formula could not be scored
+   pub cost: usize,
+   pub improvement: bool,
+   pub formula: String
+}
+
+/// encapsulate a result but records every improvement (and possibly a sample of the evaluations)
+/// the history can then be exported as CSV or JSON lines to plot convergence curves
+/// NOTE: the searches create their result themselves,
+///       SAMPLING_PERIOD is the initial sampling period of a history (see `set_sampling_period`)
+pub struct History<ResultType, const SAMPLING_PERIOD: usize = 0>
+{
+   result: ResultType,
+   start: Instant,
+   nb_evaluations: usize,
+   sampling_period: usize, // an evaluation is recorded every sampling_period evaluations, 0 meaning never
+   records: Vec<Record>
+}
+
+//-------------------------------------------------------------------------------------------------
+// FUNCTIONS
+
+/// escapes a string to be put between quotes in a CSV file
+fn escape_csv(s: &str) -> String
+{
+   s.replace('"', "\"\"")
+}
+
+/// escapes a string to be put between quotes in a JSON file
+fn escape_json(s: &str) -> String
+{
+   let mut result = String::with_capacity(s.len());
+   for c in s.chars()
+   {
+      match c
+      {
+         '"' => result.push_str("\\\""),
+         '\\' => result.push_str("\\\\"),
+         '\n' => result.push_str("\\n"),
+         '\t' => result.push_str("\\t"),
+         '\r' => result.push_str("\\r"),
+         c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+         c => result.push(c)
+      }
+   }
+   result
+}
+
+/// turns a score into a string, using the given string for missing or non finite scores
+fn score_to_string(score: Option<f64>, missing: &str) -> String
+{
+   match score
+   {
+      Some(score) if score.is_finite() => score.to_string(),
+      _ => missing.to_string()
+   }
+}
+
+impl<ResultType, const SAMPLING_PERIOD: usize> History<ResultType, SAMPLING_PERIOD>
+{
+   /// records one evaluation every period evaluations, in addition to the improvements
+   /// a period of 0 records only the improvements (which is the default SAMPLING_PERIOD)
+   pub fn set_sampling_period(&mut self, period: usize)
+   {
+      self.sampling_period = period;
+   }
+
+   /// returns the underlying result
+   pub fn get_result(&self) -> &ResultType
+   {
+      &self.result
+   }
+
+   /// returns the evaluations recorded so far
+   pub fn records(&self) -> &[Record]
+   {
+      &self.records
+   }
+
+   /// writes the history in a CSV file
+   pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()>
+   {
+      let mut file = BufWriter::new(File::create(path)?);
+      self.write_csv_records(&mut file)?;
+      file.flush()
+   }
+
+   /// writes the history in a JSON lines file (one JSON object per line)
+   pub fn write_json_lines<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()>
+   {
+      let mut file = BufWriter::new(File::create(path)?);
+      self.write_json_records(&mut file)?;
+      file.flush()
+   }
+
+   /// writes the header and the records in CSV format
+   fn write_csv_records<W: Write>(&self, writer: &mut W) -> std::io::Result<()>
+   {
+      writeln!(writer, "iteration,elapsed_seconds,score,cost,improvement,formula")?;
+      for record in self.records.iter()
+      {
+         writeln!(writer,
+                  "{},{},{},{},{},\"{}\"",
+                  record.iteration,
+                  record.elapsed_seconds,
+                  score_to_string(record.score, ""),
+                  record.cost,
+                  record.improvement,
+                  escape_csv(&record.formula))?;
+      }
+      Ok(())
+   }
+
+   /// writes the records as JSON objects, one per line
+   fn write_json_records<W: Write>(&self, writer: &mut W) -> std::io::Result<()>
+   {
+      for record in self.records.iter()
+      {
+         writeln!(writer,
+                  concat!("{{\"iteration\":{},\"elapsed_seconds\":{},\"score\":{},",
+                          "\"cost\":{},\"improvement\":{},\"formula\":\"{}\"}}"),
+                  record.iteration,
+                  record.elapsed_seconds,
+                  score_to_string(record.score, "null"),
+                  record.cost,
+                  record.improvement,
+                  escape_json(&record.formula))?;
+      }
+      Ok(())
+   }
+}
+
+//-------------------------------------------------------------------------------------------------
+// TRAIT
+
+/// implements the display trait needed by the result trait
+impl<ResultType: Display, const SAMPLING_PERIOD: usize> Display for History<ResultType, SAMPLING_PERIOD>
+{
+   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+   {
+      write!(f, "{}", self.result)
+   }
+}
+
+/// implements the result trait
+impl<State, ResultType, const SAMPLING_PERIOD: usize> Result<State> for History<ResultType, SAMPLING_PERIOD>
+   where State: Grammar,
+         ResultType: Result<State>,
+         ResultType::ScoreType: Copy + Into<Option<f64>>
+{
+   type ScoreType = ResultType::ScoreType;
+
+   fn new() -> Self
+   {
+      History { result: ResultType::new(),
+                start: Instant::now(),
+                nb_evaluations: 0,
+                sampling_period: SAMPLING_PERIOD,
+                records: Vec::new() }
+   }
+
+   fn best(&self) -> (Formula<State>, f64)
+   {
+      self.result.best()
+   }
+
+   /// update the result and records the evaluation if it is an improvement or if it is sampled
+   fn update(&mut self, formula: Formula<State>, score: Self::ScoreType) -> bool
+   {
+      self.nb_evaluations += 1;
+      let sampled = (self.sampling_period != 0) && self.nb_evaluations.is_multiple_of(self.sampling_period);
+      let record = if sampled { Some((formula.to_string(), formula.cost())) } else { None };
+      let improvement = self.result.update(formula, score);
+      if improvement || sampled
+      {
+         let (formula, cost) = match record
+         {
+            Some(record) => record,
+            None =>
+            {
+               let (formula, _) = self.result.best();
+               (formula.to_string(), formula.cost())
+            }
+         };
+         let elapsed = self.start.elapsed();
+         let elapsed_seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9;
+         self.records.push(Record { iteration: self.nb_evaluations,
+                                    elapsed_seconds,
+                                    score: score.into(),
+                                    cost,
+                                    improvement,
+                                    formula });
+      }
+      improvement
+   }
+
+   fn search_exhausted(&mut self, nb_formulas: usize)
+   {
+      self.result.search_exhausted(nb_formulas)
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use crate::result::Single;
+
+   /// formulas made of a single state, the state being displayed with characters that need escaping
+   #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+   enum State
+   {
+      Quoted,
+      Plain
+   }
+
+   impl Grammar for State
+   {
+      type ScoreType = f64;
+
+      fn root_state() -> Self
+      {
+         State::Quoted
+      }
+
+      fn expand(self) -> Vec<Vec<Self>>
+      {
+         vec![]
+      }
+
+      fn to_string(formula: &Formula<Self>) -> String
+      {
+         match formula[0]
+         {
+            State::Quoted => "f(\"x\", y)\nz".to_string(),
+            State::Plain => "x".to_string()
+         }
+      }
+
+      fn evaluate(_formula: &Formula<Self>) -> Self::ScoreType
+      {
+         0.
+      }
+   }
+
+   /// builds a formula made of a single state
+   fn formula(state: State) -> Formula<State>
+   {
+      let mut formula = Formula::empty();
+      formula.push(state);
+      formula
+   }
+
+   /// returns a history with a single record, whose formula needs escaping
+   fn quoted_history() -> History<Single<State>>
+   {
+      let mut history = History::<Single<State>>::new();
+      history.update(formula(State::Quoted), 1.5);
+      history
+   }
+
+   #[test]
+   fn quotes_csv_fields()
+   {
+      let mut csv = Vec::new();
+      quoted_history().write_csv_records(&mut csv).unwrap();
+      let csv = String::from_utf8(csv).unwrap();
+      let mut lines = csv.splitn(2, '\n');
+      assert_eq!(lines.next(), Some("iteration,elapsed_seconds,score,cost,improvement,formula"));
+      let record = lines.next().unwrap();
+      // the comma and the newline are protected by the quotes, the quotes are doubled
+      assert!(record.starts_with("1,"));
+      assert!(record.ends_with(",1.5,1,true,\"f(\"\"x\"\", y)\nz\"\n"));
+   }
+
+   #[test]
+   fn escapes_json_strings()
+   {
+      let mut json = Vec::new();
+      quoted_history().write_json_records(&mut json).unwrap();
+      let json = String::from_utf8(json).unwrap();
+      // a single line per record
+      assert_eq!(json.lines().count(), 1);
+      assert!(json.starts_with("{\"iteration\":1,\"elapsed_seconds\":"));
+      assert!(json.ends_with(concat!(",\"score\":1.5,\"cost\":1,\"improvement\":true,",
+                                     "\"formula\":\"f(\\\"x\\\", y)\\nz\"}\n")));
+   }
+
+   #[test]
+   fn records_improvements_and_samples()
+   {
+      let mut history = History::<Single<State>, 3>::new();
+      for score in &[1., 0., 0., 2., 0., 0.]
+      {
+         history.update(formula(State::Plain), *score);
+      }
+      let records: Vec<(usize, bool)> =
+         history.records().iter().map(|record| (record.iteration, record.improvement)).collect();
+      assert_eq!(records, [(1, true), (3, false), (4, true), (6, false)]);
+      assert_eq!(history.best().1, 2.);
+   }
+
+   #[test]
+   fn the_sampling_period_can_be_changed()
+   {
+      let mut history = History::<Single<State>, 3>::new();
+      history.set_sampling_period(0);
+      for score in &[1., 0., 0., 2.]
+      {
+         history.update(formula(State::Plain), *score);
+      }
+      assert!(history.records().iter().all(|record| record.improvement));
+   }
+}
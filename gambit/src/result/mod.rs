@@ -1,13 +1,19 @@
 pub mod single;
 pub mod pareto;
+pub mod top_k;
+pub mod history;
 pub mod display;
 pub mod option;
+pub mod exhaustion;
 
 use crate::grammar::{Grammar, Formula};
 pub use single::Single;
-pub use pareto::ParetoFront;
+pub use pareto::{ParetoFront, MultiObjectiveFront};
+pub use top_k::TopK;
+pub use history::History;
 pub use display::DisplayProgress;
 pub use option::Optional;
+pub use exhaustion::Exhaustion;
 
 /// represents a result of the algorithm
 pub trait Result<State>: std::fmt::Display
@@ -23,4 +29,9 @@ pub trait Result<State>: std::fmt::Display
 
    /// updates the result with a f64 score, returns true if the result is better than the best so far
    fn update(&mut self, formula: Formula<State>, score: Self::ScoreType) -> bool;
+
+   /// called when the search stops because there is no formula left to explore
+   /// with the number of distinct formulas evaluated (see `Observer::search_exhausted`)
+   /// NOTE: does nothing by default (see `Exhaustion`)
+   fn search_exhausted(&mut self, _nb_formulas: usize) {}
 }
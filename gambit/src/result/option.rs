@@ -52,4 +52,9 @@ impl<State, ResultType, UnderlyingScoreType> Result<State> for Optional<ResultTy
          false
       }
    }
+
+   fn search_exhausted(&mut self, nb_formulas: usize)
+   {
+      self.0.search_exhausted(nb_formulas)
+   }
 }
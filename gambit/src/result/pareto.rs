@@ -1,6 +1,6 @@
 use crate::grammar::{Grammar, Formula};
 use super::Result;
-use linked_list::{LinkedList, Cursor};
+use float_ord::FloatOrd;
 use std::fmt;
 
 //-------------------------------------------------------------------------------------------------
@@ -14,45 +14,114 @@ struct ParetoElement<State: Grammar>
    cost: usize
 }
 
-/// stores a pareto front of the results so far
+/// stores a pareto front of the (score, cost) of the results so far
+/// the front is sorted by decreasing score (and thus decreasing cost)
 pub struct ParetoFront<State: Grammar>
 {
-   front: LinkedList<ParetoElement<State>>
+   front: Vec<ParetoElement<State>>
 }
 
+/// represents an individual result stored in the multi-objective front
+struct MultiObjectiveElement<State: Grammar>
+{
+   formula: Formula<State>,
+   objectives: Vec<f64>
+}
+
+/// stores the non dominated results so far according to several objectives (see Grammar::objectives)
+/// NOTE: all objectives are maximized, the first objective is considered to be the score
+pub struct MultiObjectiveFront<State: Grammar>
+{
+   front: Vec<MultiObjectiveElement<State>>
+}
+
+//-------------------------------------------------------------------------------------------------
+// FUNCTIONS
+
 /// inserts a new element in the pareto front
 /// returns true if it is better than the best element so far
-fn insert<State: Grammar>(mut front_cursor: Cursor<ParetoElement<State>>,
-                          new_element: ParetoElement<State>)
-                          -> bool
+/// NOTE: an element equal to an element of the front is considered dominated
+///       an element with a NaN score cannot be compared with the front and is rejected
+fn insert<State: Grammar>(front: &mut Vec<ParetoElement<State>>, new_element: ParetoElement<State>) -> bool
 {
-   match front_cursor.peek_next()
+   if new_element.score.is_nan()
    {
-      None =>
-      {
-         front_cursor.insert(new_element);
-         true
-      }
-      Some(ref element) if element.score <= new_element.score && element.cost >= new_element.cost =>
-      {
-         // we pareto dominate this result
-         front_cursor.remove();
-         insert(front_cursor, new_element)
-      }
-      Some(ref element) if element.score < new_element.score =>
+      return false;
+   }
+   // elements with a score greater or equal to the new score form a prefix of the front
+   let nb_better_or_equal =
+      front.iter().take_while(|element| element.score >= new_element.score).count();
+   // among them, the last one is the cheapest
+   if nb_better_or_equal > 0 && front[nb_better_or_equal - 1].cost <= new_element.cost
+   {
+      // we are pareto dominated
+      return false;
+   }
+   // elements with a lower or equal score and a greater or equal cost are dominated
+   // they are contiguous and start after the elements with a strictly better score
+   let nb_better = front.iter().take_while(|element| element.score > new_element.score).count();
+   let nb_dominated =
+      front[nb_better..].iter().take_while(|element| element.cost >= new_element.cost).count();
+   front.drain(nb_better..nb_better + nb_dominated);
+   front.insert(nb_better, new_element);
+   nb_better == 0
+}
+
+/// returns true if the first objectives dominate or are equal to the second objectives
+fn dominates_or_equal(objectives: &[f64], other_objectives: &[f64]) -> bool
+{
+   objectives.iter().zip(other_objectives).all(|(x, y)| x >= y)
+}
+
+impl<State: Grammar> ParetoFront<State>
+{
+   /// iterates on the (formula, score, cost) of the front, by decreasing score
+   pub fn iter(&self) -> impl Iterator<Item = (&Formula<State>, f64, usize)>
+   {
+      self.front.iter().map(|element| (&element.formula, element.score, element.cost))
+   }
+
+   /// exports the front as a vector of (formula, score, cost), by decreasing score
+   pub fn to_vec(&self) -> Vec<(Formula<State>, f64, usize)>
+   {
+      self.iter().map(|(formula, score, cost)| (formula.clone(), score, cost)).collect()
+   }
+}
+
+impl<State: Grammar> MultiObjectiveFront<State>
+{
+   /// iterates on the (formula, objectives) of the front, in no particular order
+   pub fn iter(&self) -> impl Iterator<Item = (&Formula<State>, &[f64])>
+   {
+      self.front.iter().map(|element| (&element.formula, element.objectives.as_slice()))
+   }
+
+   /// exports the front as a vector of (formula, objectives), in no particular order
+   pub fn to_vec(&self) -> Vec<(Formula<State>, Vec<f64>)>
+   {
+      self.iter().map(|(formula, objectives)| (formula.clone(), objectives.to_vec())).collect()
+   }
+
+   /// inserts a formula with the given objectives if it is not dominated
+   /// returns true if it has a better first objective than all the elements of the front
+   /// NOTE: objectives that cannot be compared with the front are rejected
+   ///       (no objective, a NaN objective or a number of objectives different from the front's)
+   pub fn insert(&mut self, formula: Formula<State>, objectives: Vec<f64>) -> bool
+   {
+      let dimension = self.front.first().map_or(objectives.len(), |element| element.objectives.len());
+      if objectives.is_empty() || (objectives.len() != dimension) || objectives.iter().any(|x| x.is_nan())
       {
-         // we are better but more expensive
-         front_cursor.insert(new_element);
-         true
+         return false;
       }
-      Some(ref element) if element.cost > new_element.cost =>
+      if self.front.iter().any(|element| dominates_or_equal(&element.objectives, &objectives))
       {
-         // we are worst but cheaper
-         front_cursor.next();
-         insert(front_cursor, new_element);
-         false
+         // we are pareto dominated
+         return false;
       }
-      _ => false // we are pareto dominated
+      let improvement = self.front.iter().all(|element| objectives[0] > element.objectives[0]);
+      self.front.retain(|element| !dominates_or_equal(&objectives, &element.objectives));
+      self.front.push(MultiObjectiveElement { formula, objectives });
+      improvement
    }
 }
 
@@ -64,10 +133,10 @@ impl<State: Grammar> fmt::Display for ParetoFront<State>
 {
    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
    {
-      writeln!(f, "{{").unwrap();
+      writeln!(f, "{{")?;
       for element in self.front.iter()
       {
-         writeln!(f, "\tscore:{}\tcost:{}\tformula:'{}'", element.score, element.cost, element.formula).unwrap();
+         writeln!(f, "\tscore:{}\tcost:{}\tformula:'{}'", element.score, element.cost, element.formula)?;
       }
       writeln!(f, "}}")
    }
@@ -80,13 +149,13 @@ impl<State: Grammar> Result<State> for ParetoFront<State>
    /// creates an empty result
    fn new() -> ParetoFront<State>
    {
-      ParetoFront { front: LinkedList::new() }
+      ParetoFront { front: Vec::new() }
    }
 
    /// returns the best (formula, score) so far
    fn best(&self) -> (Formula<State>, f64)
    {
-      match self.front.front()
+      match self.front.first()
       {
          None => (Formula::<State>::empty(), std::f64::NEG_INFINITY),
          Some(ParetoElement { formula, score, .. }) => (formula.clone(), *score)
@@ -98,6 +167,202 @@ impl<State: Grammar> Result<State> for ParetoFront<State>
    {
       let cost = formula.cost();
       let new_element = ParetoElement { formula, score, cost };
-      insert(self.front.cursor(), new_element)
+      insert(&mut self.front, new_element)
    }
-}
\ No newline at end of file
+}
+
+/// macro to display a result
+impl<State: Grammar> fmt::Display for MultiObjectiveFront<State>
+{
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+   {
+      writeln!(f, "{{")?;
+      for element in self.front.iter()
+      {
+         writeln!(f, "\tobjectives:{:?}\tformula:'{}'", element.objectives, element.formula)?;
+      }
+      writeln!(f, "}}")
+   }
+}
+
+impl<State: Grammar> Result<State> for MultiObjectiveFront<State>
+{
+   type ScoreType = f64;
+
+   /// creates an empty result
+   fn new() -> MultiObjectiveFront<State>
+   {
+      MultiObjectiveFront { front: Vec::new() }
+   }
+
+   /// returns the (formula, score) with the best score (first objective) so far
+   fn best(&self) -> (Formula<State>, f64)
+   {
+      self.front
+          .iter()
+          .max_by_key(|element| FloatOrd(element.objectives[0]))
+          .map(|element| (element.formula.clone(), element.objectives[0]))
+          .unwrap_or((Formula::<State>::empty(), std::f64::NEG_INFINITY))
+   }
+
+   /// if the result is non dominated by the front so far, we update it
+   fn update(&mut self, formula: Formula<State>, score: Self::ScoreType) -> bool
+   {
+      let objectives = State::objectives(&formula, score);
+      self.insert(formula, objectives)
+   }
+}
+
+//-------------------------------------------------------------------------------------------------
+// TESTS
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   /// a grammar whose formulas are sequences of units, the cost of a formula being its length
+   #[derive(Copy, Clone, PartialEq, Eq, Hash)]
+   struct Unit;
+
+   impl Grammar for Unit
+   {
+      type ScoreType = f64;
+
+      fn root_state() -> Unit
+      {
+         Unit
+      }
+
+      fn expand(self) -> Vec<Vec<Unit>>
+      {
+         vec![]
+      }
+
+      fn to_string(formula: &Formula<Unit>) -> String
+      {
+         format!("{} units", formula.len())
+      }
+
+      fn evaluate(_formula: &Formula<Unit>) -> f64
+      {
+         0.
+      }
+   }
+
+   /// builds a formula of the given cost
+   fn formula(cost: usize) -> Formula<Unit>
+   {
+      let mut formula = Formula::empty();
+      formula.extend((0..cost).map(|_| Unit));
+      formula
+   }
+
+   /// returns the (score, cost) of the front
+   fn content(front: &ParetoFront<Unit>) -> Vec<(f64, usize)>
+   {
+      front.iter().map(|(_, score, cost)| (score, cost)).collect()
+   }
+
+   #[test]
+   fn insert_in_empty_front()
+   {
+      let mut front = ParetoFront::new();
+      assert!(front.update(formula(3), 1.));
+      assert_eq!(content(&front), vec![(1., 3)]);
+      assert_eq!(front.best().1, 1.);
+   }
+
+   #[test]
+   fn reject_dominated_and_equal_elements()
+   {
+      let mut front = ParetoFront::new();
+      front.update(formula(3), 1.);
+      assert!(!front.update(formula(4), 0.)); // worse and more expensive
+      assert!(!front.update(formula(3), 0.)); // worse and same cost
+      assert!(!front.update(formula(4), 1.)); // same score and more expensive
+      assert!(!front.update(formula(3), 1.)); // equal
+      assert_eq!(content(&front), vec![(1., 3)]);
+   }
+
+   #[test]
+   fn keep_tradeoffs_sorted()
+   {
+      let mut front = ParetoFront::new();
+      front.update(formula(3), 1.);
+      assert!(!front.update(formula(1), 0.)); // worse but cheaper, kept but not an improvement
+      assert!(front.update(formula(5), 2.)); // better but more expensive
+      assert!(!front.update(formula(2), 0.5)); // in between
+      assert_eq!(content(&front), vec![(2., 5), (1., 3), (0.5, 2), (0., 1)]);
+   }
+
+   #[test]
+   fn remove_all_dominated_elements()
+   {
+      let mut front = ParetoFront::new();
+      front.update(formula(5), 2.);
+      front.update(formula(3), 1.);
+      front.update(formula(2), 0.5);
+      front.update(formula(1), 0.);
+      assert!(!front.update(formula(2), 1.5)); // dominates (1,3) and (0.5,2)
+      assert_eq!(content(&front), vec![(2., 5), (1.5, 2), (0., 1)]);
+      assert!(front.update(formula(1), 3.)); // dominates everything
+      assert_eq!(content(&front), vec![(3., 1)]);
+   }
+
+   #[test]
+   fn same_score_cheaper_and_same_cost_better()
+   {
+      let mut front = ParetoFront::new();
+      front.update(formula(3), 1.);
+      assert!(front.update(formula(2), 1.)); // same score but cheaper
+      assert_eq!(content(&front), vec![(1., 2)]);
+      assert!(front.update(formula(2), 2.)); // same cost but better
+      assert_eq!(content(&front), vec![(2., 2)]);
+   }
+
+   #[test]
+   fn reject_nan_scores()
+   {
+      let mut front = ParetoFront::new();
+      assert!(!front.update(formula(3), std::f64::NAN));
+      assert!(front.iter().next().is_none());
+      front.update(formula(3), 1.);
+      front.update(formula(1), 0.);
+      assert!(!front.update(formula(2), std::f64::NAN));
+      assert_eq!(content(&front), vec![(1., 3), (0., 1)]);
+      assert_eq!(front.best().1, 1.);
+   }
+
+   #[test]
+   fn multi_objective_front()
+   {
+      let mut front = MultiObjectiveFront::<Unit>::new();
+      assert!(front.insert(formula(1), vec![1., 0., 0.]));
+      assert!(!front.insert(formula(1), vec![0., 1., 0.]));
+      assert!(!front.insert(formula(1), vec![0., 0., 1.]));
+      assert!(!front.insert(formula(1), vec![0., 0., 0.])); // dominated
+      assert!(!front.insert(formula(1), vec![1., 0., 0.])); // equal
+      assert_eq!(front.iter().count(), 3);
+      assert!(front.insert(formula(1), vec![2., 1., 0.])); // dominates two elements
+      let mut objectives: Vec<Vec<f64>> = front.to_vec().into_iter().map(|(_, o)| o).collect();
+      objectives.sort_by(|a, b| a.partial_cmp(b).unwrap());
+      assert_eq!(objectives, vec![vec![0., 0., 1.], vec![2., 1., 0.]]);
+      assert_eq!(front.best().1, 2.);
+   }
+
+   #[test]
+   fn multi_objective_front_rejects_incomparable_objectives()
+   {
+      let mut front = MultiObjectiveFront::<Unit>::new();
+      assert!(!front.insert(formula(1), vec![]));
+      assert!(!front.insert(formula(1), vec![std::f64::NAN, 0.]));
+      assert_eq!(front.iter().count(), 0);
+      assert!(front.insert(formula(1), vec![1., 0.]));
+      assert!(!front.insert(formula(1), vec![0., std::f64::NAN]));
+      assert!(!front.insert(formula(1), vec![2.])); // would dominate if truncated
+      assert!(!front.insert(formula(1), vec![0., 1., 5.])); // would not be dominated if truncated
+      assert_eq!(front.to_vec().into_iter().map(|(_, o)| o).collect::<Vec<_>>(), vec![vec![1., 0.]]);
+      assert_eq!(front.best().1, 1.);
+   }
+}
@@ -0,0 +1,234 @@
+use super::Result;
+use crate::grammar::{Grammar, Formula};
+use std::fmt;
+
+//-------------------------------------------------------------------------------------------------
+// TYPES
+
+/// represents an individual result stored in the hall of fame
+struct TopKElement<State: Grammar>
+{
+   formula: Formula<State>,
+   canonical_form: Formula<State>,
+   score: f64
+}
+
+/// stores the K (10 by default) best distinct formulas so far, sorted by decreasing score
+/// NOTE: two formulas are considered identical if they have the same canonical form
+///       (see Grammar::canonical_form)
+pub struct TopK<State: Grammar, const K: usize = 10>
+{
+   elements: Vec<TopKElement<State>>
+}
+
+impl<State: Grammar, const K: usize> TopK<State, K>
+{
+   /// iterates on the (formula, score) stored, from the best to the worst
+   pub fn iter(&self) -> impl Iterator<Item = (&Formula<State>, f64)>
+   {
+      self.elements.iter().map(|element| (&element.formula, element.score))
+   }
+
+   /// returns the number of formulas stored
+   pub fn len(&self) -> usize
+   {
+      self.elements.len()
+   }
+
+   /// returns true if no formula has been stored yet
+   pub fn is_empty(&self) -> bool
+   {
+      self.elements.is_empty()
+   }
+}
+
+//-------------------------------------------------------------------------------------------------
+// TRAIT
+
+/// macro to display a result
+impl<State: Grammar, const K: usize> fmt::Display for TopK<State, K>
+{
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+   {
+      writeln!(f, "{{")?;
+      for element in self.elements.iter()
+      {
+         writeln!(f, "\tscore:{}\tformula:'{}'", element.score, element.formula)?;
+      }
+      writeln!(f, "}}")
+   }
+}
+
+impl<State: Grammar, const K: usize> Result<State> for TopK<State, K>
+{
+   type ScoreType = f64;
+
+   /// creates an empty result
+   fn new() -> TopK<State, K>
+   {
+      TopK { elements: Vec::with_capacity(K + 1) }
+   }
+
+   /// returns the best (formula, score) so far
+   fn best(&self) -> (Formula<State>, f64)
+   {
+      match self.elements.first()
+      {
+         None => (Formula::<State>::empty(), std::f64::NEG_INFINITY),
+         Some(TopKElement { formula, score, .. }) => (formula.clone(), *score)
+      }
+   }
+
+   /// if the formula is among the K best distinct formulas, we insert it
+   /// NOTE: a formula with a NaN score cannot be compared with the others and is rejected
+   fn update(&mut self, formula: Formula<State>, score: Self::ScoreType) -> bool
+   {
+      if (K == 0) || score.is_nan()
+      {
+         return false;
+      }
+      if (self.elements.len() == K) && (score <= self.elements[K - 1].score)
+      {
+         // not good enough to enter the hall of fame
+         return false;
+      }
+      let canonical_form = formula.canonical_form();
+      if let Some(index) = self.elements.iter().position(|element| element.canonical_form == canonical_form)
+      {
+         if score <= self.elements[index].score
+         {
+            // a better equivalent formula is already stored
+            return false;
+         }
+         self.elements.remove(index);
+      }
+      let index =
+         self.elements.iter().position(|element| score > element.score).unwrap_or(self.elements.len());
+      self.elements.insert(index, TopKElement { formula, canonical_form, score });
+      self.elements.truncate(K);
+      index == 0
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   /// sums of two variables, the sum being commutative
+   #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+   enum State
+   {
+      Add,
+      X,
+      Y
+   }
+
+   impl Grammar for State
+   {
+      type ScoreType = f64;
+
+      fn root_state() -> Self
+      {
+         State::Add
+      }
+
+      fn expand(self) -> Vec<Vec<Self>>
+      {
+         vec![]
+      }
+
+      fn to_string(formula: &Formula<Self>) -> String
+      {
+         format!("{:?}", formula.iter().collect::<Vec<_>>())
+      }
+
+      fn evaluate(_formula: &Formula<Self>) -> Self::ScoreType
+      {
+         0.
+      }
+
+      /// sorts the operands of the sum
+      fn canonical_form(formula: &Formula<Self>) -> Formula<Self>
+      {
+         let mut canonical_form = formula.clone();
+         canonical_form[1..].sort();
+         canonical_form
+      }
+   }
+
+   /// builds a formula from a slice of states
+   fn formula(states: &[State]) -> Formula<State>
+   {
+      let mut formula = Formula::empty();
+      states.iter().for_each(|&state| formula.push(state));
+      formula
+   }
+
+   #[test]
+   fn keeps_the_k_best_formulas_sorted()
+   {
+      let mut top = TopK::<State, 2>::new();
+      assert!(top.update(formula(&[State::X]), 1.));
+      assert!(!top.update(formula(&[State::Y]), 0.));
+      assert!(top.update(formula(&[State::Add, State::X, State::X]), 2.));
+      // too low to enter the hall of fame
+      assert!(!top.update(formula(&[State::Add, State::Y, State::Y]), -1.));
+      let scores: Vec<f64> = top.iter().map(|(_, score)| score).collect();
+      assert_eq!(scores, [2., 1.]);
+      assert_eq!(top.best().1, 2.);
+   }
+
+   #[test]
+   fn deduplicates_identical_formulas()
+   {
+      let mut top = TopK::<State>::new();
+      top.update(formula(&[State::X]), 1.);
+      assert!(!top.update(formula(&[State::X]), 0.5));
+      assert_eq!(top.len(), 1);
+      assert_eq!(top.best().1, 1.);
+      // a better score replaces the previous one
+      assert!(top.update(formula(&[State::X]), 3.));
+      assert_eq!(top.len(), 1);
+      assert_eq!(top.best().1, 3.);
+   }
+
+   #[test]
+   fn deduplicates_formulas_with_the_same_canonical_form()
+   {
+      let mut top = TopK::<State>::new();
+      top.update(formula(&[State::Add, State::X, State::Y]), 1.);
+      assert!(!top.update(formula(&[State::Add, State::Y, State::X]), 0.5));
+      assert_eq!(top.len(), 1);
+      assert!(top.update(formula(&[State::Add, State::Y, State::X]), 2.));
+      assert_eq!(top.len(), 1);
+      assert!(top.best().0 == formula(&[State::Add, State::Y, State::X]));
+      // formulas with different canonical forms are both kept
+      top.update(formula(&[State::Add, State::X, State::X]), 0.);
+      assert_eq!(top.len(), 2);
+   }
+
+   #[test]
+   fn reject_nan_scores()
+   {
+      let mut top = TopK::<State, 2>::new();
+      assert!(!top.update(formula(&[State::X]), std::f64::NAN));
+      assert!(top.is_empty());
+      top.update(formula(&[State::X]), 1.);
+      top.update(formula(&[State::Y]), 0.);
+      // would otherwise replace the equivalent formula or the worst one
+      assert!(!top.update(formula(&[State::X]), std::f64::NAN));
+      assert!(!top.update(formula(&[State::Add, State::X, State::Y]), std::f64::NAN));
+      assert_eq!(top.iter().map(|(_, score)| score).collect::<Vec<_>>(), vec![1., 0.]);
+      assert_eq!(top.best().1, 1.);
+   }
+
+   #[test]
+   fn displays_every_formula()
+   {
+      let mut top = TopK::<State>::new();
+      top.update(formula(&[State::X]), 1.);
+      top.update(formula(&[State::Y]), 0.);
+      assert_eq!(top.to_string(), "{\n\tscore:1\tformula:'[X]'\n\tscore:0\tformula:'[Y]'\n}\n");
+   }
+}
@@ -0,0 +1,181 @@
+use rand::Rng;
+use std::cmp::Ordering;
+use crate::grammar::{Grammar, Formula};
+use crate::result::Result;
+use super::rollout::{Rollout, rollout_expand};
+use super::position::Position;
+
+//-----------------------------------------------------------------------------
+// TRAIT
+
+/// a user provided estimation of the quality of a partial formula, used to sort the positions of a beam
+pub trait Heuristic<State: Grammar>
+{
+   /// estimates the score of the best formula that can be derived from a partial formula
+   /// the stack contains the states that are still to be expanded, the last one being expanded next
+   /// NOTE: takes `&mut self` such that the heuristic can keep statistics or a cache
+   fn estimate(&mut self, formula: &Formula<State>, stack: &[State]) -> State::ScoreType;
+}
+
+//-----------------------------------------------------------------------------
+// FUNCTIONS
+
+/// estimates the quality of a position with an expansion following the rollout
+/// returns None if there was no evaluation left
+pub fn rollout_estimate<State, RNG, Res, Roll>(position: &Position<State>,
+                                               rollout: &mut Roll,
+                                               rng: &mut RNG,
+                                               result: &mut Res,
+                                               nb_evaluations_left: &mut usize)
+                                               -> Option<State::ScoreType>
+   where State: Grammar,
+         RNG: Rng,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Roll: Rollout<State>
+{
+   if *nb_evaluations_left == 0
+   {
+      return None;
+   }
+   *nb_evaluations_left -= 1;
+   let formula = position.formula.clone();
+   let stack = position.stack.clone();
+   let (formula, score) = rollout_expand(formula, stack, rng, position.available_depth, rollout, |_| ());
+   result.update(formula, score);
+   Some(score)
+}
+
+/// performs a beam search from the root of the grammar
+/// at each decision, all the rules of all the positions in the beam are played
+/// and the beam_width best positions (according to `estimate`) are kept
+/// the formulas completed along the way are evaluated and given to the result
+/// the search stops once all formulas are complete or there is no evaluation left (`estimate` returning None)
+pub fn beam<State, Res, Estimate>(available_depth: usize,
+                                  beam_width: usize,
+                                  result: &mut Res,
+                                  nb_evaluations_left: &mut usize,
+                                  mut estimate: Estimate)
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Estimate: FnMut(&Position<State>, &mut Res, &mut usize) -> Option<State::ScoreType>
+{
+   let mut positions = vec![Position::<State>::root(available_depth)];
+   while !positions.is_empty()
+   {
+      // plays all the rules of all the positions
+      let mut candidates = Vec::new();
+      for mut position in positions.drain(..)
+      {
+         match position.next_decision()
+         {
+            None =>
+            {
+               // complete formula
+               if *nb_evaluations_left == 0
+               {
+                  return;
+               }
+               *nb_evaluations_left -= 1;
+               let score = position.formula.evaluate();
+               result.update(position.formula, score);
+            }
+            Some(rules) =>
+            {
+               for rule in rules.iter()
+               {
+                  let mut candidate = position.clone();
+                  candidate.play(rule);
+                  match estimate(&candidate, result, nb_evaluations_left)
+                  {
+                     None => return, // no evaluation left
+                     Some(score) => candidates.push((candidate, score))
+                  }
+               }
+            }
+         }
+      }
+      // keeps the best candidates
+      candidates.sort_by(|(_, score1), (_, score2)| score2.partial_cmp(score1).unwrap_or(Ordering::Equal));
+      candidates.truncate(beam_width);
+      positions.extend(candidates.into_iter().map(|(candidate, _)| candidate));
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+   use crate::result::Single;
+   use super::super::rollout::RandomRollout;
+   use super::super::test_grammar::Bits;
+
+   /// counts the ones written so far (in the formula or still on the stack), and the number of estimations
+   struct CountOnes
+   {
+      nb_estimations: usize
+   }
+
+   impl Heuristic<Bits> for CountOnes
+   {
+      fn estimate(&mut self, formula: &Formula<Bits>, stack: &[Bits]) -> f64
+      {
+         self.nb_estimations += 1;
+         formula.evaluate() + stack.iter().filter(|&&state| state == Bits::One).count() as f64
+      }
+   }
+
+   /// runs a beam search guided by the heuristic, returns its result and the evaluations left
+   fn beam_heuristic(beam_width: usize,
+                     nb_evaluations: usize,
+                     heuristic: &mut CountOnes)
+                     -> (Single<Bits>, usize)
+   {
+      let mut result = Single::new();
+      let mut nb_evaluations_left = nb_evaluations;
+      beam(100, beam_width, &mut result, &mut nb_evaluations_left, |position, _, _| {
+         Some(heuristic.estimate(&position.formula, &position.stack))
+      });
+      (result, nb_evaluations_left)
+   }
+
+   #[test]
+   fn follows_the_heuristic()
+   {
+      let mut heuristic = CountOnes { nb_estimations: 0 };
+      let (result, nb_evaluations_left) = beam_heuristic(1, 1000, &mut heuristic);
+      // a single formula is completed, both rules of each of the 8 decisions being estimated
+      assert_eq!(nb_evaluations_left, 999);
+      assert_eq!(heuristic.nb_estimations, 16);
+      assert_eq!(result.score, 8.);
+   }
+
+   #[test]
+   fn a_wide_beam_completes_every_formula()
+   {
+      let mut heuristic = CountOnes { nb_estimations: 0 };
+      let (result, nb_evaluations_left) = beam_heuristic(256, 1000, &mut heuristic);
+      assert_eq!(nb_evaluations_left, 1000 - 256);
+      assert_eq!(result.score, 8.);
+      // stops once the evaluations are exhausted
+      let (_, nb_evaluations_left) = beam_heuristic(256, 100, &mut heuristic);
+      assert_eq!(nb_evaluations_left, 0);
+   }
+
+   #[test]
+   fn rollouts_count_as_evaluations()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut result = Single::<Bits>::new();
+      let mut nb_evaluations_left = 1000;
+      beam(100, 2, &mut result, &mut nb_evaluations_left, |position, result, nb_evaluations_left| {
+         rollout_estimate(position, &mut RandomRollout, &mut rng, result, nb_evaluations_left)
+      });
+      // two rollouts per kept position and per decision, the beam being full after the first decision
+      // then two completed formulas
+      assert_eq!(nb_evaluations_left, 1000 - (2 + 7 * 4) - 2);
+      assert!(result.score >= 7.);
+   }
+}
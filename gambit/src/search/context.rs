@@ -0,0 +1,70 @@
+use crate::distribution::Distribution;
+use super::widening::NoWidening;
+
+/// the parameters of a monte carlo tree search
+pub struct MctsParameters<Widen>
+{
+   pub widening: Widen // limits the number of children explored in each node (see `Widening`)
+}
+
+impl Default for MctsParameters<NoWidening>
+{
+   fn default() -> Self
+   {
+      MctsParameters { widening: NoWidening }
+   }
+}
+
+/// the elements shared by all the calls to `expand` (and `no_expand`) during a search
+/// NOTE: the rules chosen during the current iteration are recorded
+///       so that each node can update the statistics of its rules (see `Distribution::update_rule`)
+pub struct Context<State, RNG, Norm, Widen>
+{
+   pub rng: RNG,
+   pub normalizer: Norm,
+   pub widening: Widen,
+   pub iteration: usize,
+   choices: Vec<(State, usize)> // (state, rule index) chosen since the start of the iteration
+}
+
+impl<State, RNG, Norm, Widen> Context<State, RNG, Norm, Widen>
+   where State: Eq + Copy
+{
+   /// creates a new context
+   pub fn new(rng: RNG, normalizer: Norm, widening: Widen) -> Self
+   {
+      Context { rng, normalizer, widening, iteration: 0, choices: Vec::new() }
+   }
+
+   /// prepares the context for a new iteration
+   pub fn start_iteration(&mut self, iteration: usize)
+   {
+      self.iteration = iteration;
+      self.choices.clear();
+   }
+
+   /// records that a rule was chosen to expand a state, returns the position of the choice
+   pub fn choose(&mut self, state: State, rule_index: usize) -> usize
+   {
+      self.choices.push((state, rule_index));
+      self.choices.len() - 1
+   }
+
+   /// updates the statistics of the rules of a node with the score of the iteration
+   /// using all the choices done, for the state of the node, since the given position
+   pub fn update_rules<Distr: Distribution>(&self,
+                                            distribution: &mut Distr,
+                                            state: State,
+                                            first_choice: usize,
+                                            score: Distr::ScoreType)
+      where Distr::ScoreType: Copy
+   {
+      for &(chosen_state, rule_index) in &self.choices[first_choice..]
+      {
+         if chosen_state == state
+         {
+            distribution.update_rule(rule_index, score);
+         }
+      }
+   }
+}
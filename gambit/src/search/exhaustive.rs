@@ -0,0 +1,359 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use crate::grammar::{Grammar, Formula};
+use super::position::Position;
+
+//-----------------------------------------------------------------------------
+// DEPTH FIRST ENUMERATION
+
+/// visits every formula that can be derived from the root of the grammar within the given depth
+/// the formulas are enumerated depth first, the first rules being explored first,
+/// following the same conventions as `random_expand` (once the depth is exhausted, the first rule is picked)
+/// the enumeration stops as soon as `visit` returns false
+/// returns true if all the formulas have been visited
+/// NOTE: the memory used is proportional to the depth times the number of rules per decision
+pub fn enumerate<State, Visit>(available_depth: usize, mut visit: Visit) -> bool
+   where State: Grammar,
+         Visit: FnMut(Formula<State>) -> bool
+{
+   let mut positions = vec![Position::<State>::root(available_depth)];
+   while let Some(mut position) = positions.pop()
+   {
+      match position.next_decision()
+      {
+         None =>
+         {
+            // complete formula
+            if !visit(position.formula)
+            {
+               return false;
+            }
+         }
+         Some(rules) =>
+         {
+            // the rules are pushed in reverse order such that the first rule is explored first
+            for rule in rules.iter().rev()
+            {
+               let mut child = position.clone();
+               child.play(rule);
+               positions.push(child);
+            }
+         }
+      }
+   }
+   true
+}
+
+//-----------------------------------------------------------------------------
+// ENUMERATION BY SIZE
+
+/// a position and its next decision, sorted by the size of its formula
+struct Pending<State: Grammar>
+{
+   size: usize,  // number of states in the formula, a lower bound on the size of the complete formula
+   index: usize, // order of creation, used to break ties
+   position: Position<State>,
+   rules: Option<Vec<Vec<State>>> // None if the formula is complete
+}
+
+impl<State: Grammar> Pending<State>
+{
+   /// applies the forced expansions of the position
+   fn new(mut position: Position<State>, index: usize) -> Pending<State>
+   {
+      let rules = position.next_decision();
+      Pending { size: position.formula.len(), index, position, rules }
+   }
+
+   /// key such that the smallest, then oldest, position is the greatest
+   fn key(&self) -> Reverse<(usize, usize)>
+   {
+      Reverse((self.size, self.index))
+   }
+}
+
+impl<State: Grammar> PartialEq for Pending<State>
+{
+   fn eq(&self, other: &Self) -> bool
+   {
+      self.key() == other.key()
+   }
+}
+
+impl<State: Grammar> Eq for Pending<State> {}
+
+impl<State: Grammar> PartialOrd for Pending<State>
+{
+   fn partial_cmp(&self, other: &Self) -> Option<Ordering>
+   {
+      Some(self.cmp(other))
+   }
+}
+
+impl<State: Grammar> Ord for Pending<State>
+{
+   fn cmp(&self, other: &Self) -> Ordering
+   {
+      self.key().cmp(&other.key())
+   }
+}
+
+/// iterates on every formula that can be derived from the root of the grammar within the given depth
+/// the formulas are produced by increasing size (number of states), ties being broken by rule order
+/// following the same conventions as `random_expand` (once the depth is exhausted, the first rule is picked)
+/// NOTE: the positions waiting to be expanded are kept in memory,
+///       which makes this iterator much more memory hungry than `enumerate`
+pub struct Enumeration<State: Grammar>
+{
+   positions: BinaryHeap<Pending<State>>,
+   nb_positions: usize
+}
+
+impl<State: Grammar> Enumeration<State>
+{
+   /// creates an enumeration of the formulas that can be derived within the given depth
+   pub fn new(available_depth: usize) -> Enumeration<State>
+   {
+      let mut positions = BinaryHeap::new();
+      positions.push(Pending::new(Position::root(available_depth), 0));
+      Enumeration { positions, nb_positions: 1 }
+   }
+}
+
+impl<State: Grammar> Iterator for Enumeration<State>
+{
+   type Item = Formula<State>;
+
+   fn next(&mut self) -> Option<Formula<State>>
+   {
+      // the size of a formula can only grow as it is expanded
+      // a complete formula is thus never smaller than the formulas that are still to be produced
+      while let Some(pending) = self.positions.pop()
+      {
+         match pending.rules
+         {
+            None => return Some(pending.position.formula),
+            Some(rules) =>
+            {
+               for rule in rules.iter()
+               {
+                  let mut child = pending.position.clone();
+                  child.play(rule);
+                  self.positions.push(Pending::new(child, self.nb_positions));
+                  self.nb_positions += 1;
+               }
+            }
+         }
+      }
+      None
+   }
+}
+
+//-----------------------------------------------------------------------------
+// COUNTING
+
+/// counts the derivations of sequences of states depending on the number of decisions they take
+/// NOTE: counts saturate at u128::MAX
+struct Counter<State: Grammar>
+{
+   complete: HashMap<(State, usize), u128>,
+   interrupted: HashMap<(State, usize), u128>
+}
+
+impl<State: Grammar> Counter<State>
+{
+   /// number of derivations of the state that take exactly nb_decisions decisions
+   fn complete(&mut self, state: State, nb_decisions: usize) -> u128
+   {
+      if let Some(&count) = self.complete.get(&(state, nb_decisions))
+      {
+         return count;
+      }
+      let rules = state.expand();
+      let count = match rules.len()
+      {
+         0 => (nb_decisions == 0) as u128,
+         1 => self.complete_sequence(&rules[0], nb_decisions),
+         _ if nb_decisions == 0 => 0,
+         _ => rules.iter().fold(0u128, |total, rule| {
+                             total.saturating_add(self.complete_sequence(rule, nb_decisions - 1))
+                          })
+      };
+      self.complete.insert((state, nb_decisions), count);
+      count
+   }
+
+   /// number of derivations of the sequence of states that take exactly nb_decisions decisions
+   fn complete_sequence(&mut self, states: &[State], nb_decisions: usize) -> u128
+   {
+      match states.split_last()
+      {
+         None => (nb_decisions == 0) as u128,
+         Some((&state, others)) => (0..=nb_decisions).fold(0u128, |total, nb_decisions_state| {
+            let count_state = self.complete(state, nb_decisions_state);
+            let count_others = self.complete_sequence(others, nb_decisions - nb_decisions_state);
+            total.saturating_add(count_state.saturating_mul(count_others))
+         })
+      }
+   }
+
+   /// number of partial derivations of the state that take exactly nb_decisions decisions
+   /// and are then left with a decision to take (which will be forced as there is no depth left)
+   fn interrupted(&mut self, state: State, nb_decisions: usize) -> u128
+   {
+      if let Some(&count) = self.interrupted.get(&(state, nb_decisions))
+      {
+         return count;
+      }
+      let rules = state.expand();
+      let count = match rules.len()
+      {
+         0 => 0,
+         1 => self.interrupted_sequence(&rules[0], nb_decisions),
+         _ if nb_decisions == 0 => 1,
+         _ => rules.iter().fold(0u128, |total, rule| {
+                             total.saturating_add(self.interrupted_sequence(rule, nb_decisions - 1))
+                          })
+      };
+      self.interrupted.insert((state, nb_decisions), count);
+      count
+   }
+
+   /// number of partial derivations of the sequence of states that take exactly nb_decisions decisions
+   /// and are then left with a decision to take
+   /// NOTE: the last state is expanded first, as with a stack
+   fn interrupted_sequence(&mut self, states: &[State], nb_decisions: usize) -> u128
+   {
+      match states.split_last()
+      {
+         None => 0,
+         Some((&state, others)) =>
+         {
+            // either the state is interrupted or it is completed and one of the other states is interrupted
+            let interrupted_state = self.interrupted(state, nb_decisions);
+            (0..=nb_decisions).fold(interrupted_state, |total, nb_decisions_state| {
+               let count_state = self.complete(state, nb_decisions_state);
+               let count_others = self.interrupted_sequence(others, nb_decisions - nb_decisions_state);
+               total.saturating_add(count_state.saturating_mul(count_others))
+            })
+         }
+      }
+   }
+}
+
+/// returns the number of formulas that can be derived from the root of the grammar within each depth
+/// (from 0 to max_depth included) following the same conventions as `random_expand`
+/// NOTE: the formulas are counted without being enumerated (counts saturate at u128::MAX)
+///       this assumes that every derivation, without decision, terminates
+pub fn count_formulas<State: Grammar>(max_depth: usize) -> Vec<u128>
+{
+   let mut counter = Counter { complete: HashMap::new(), interrupted: HashMap::new() };
+   let root = State::root_state();
+   let mut nb_complete = 0u128; // formulas that take fewer decisions than the depth
+   (0..=max_depth).map(|depth| {
+                     // formulas that take exactly depth decisions, or more (the extra decisions being forced)
+                     let nb_formulas = nb_complete.saturating_add(counter.complete(root, depth))
+                                                  .saturating_add(counter.interrupted(root, depth));
+                     nb_complete = nb_complete.saturating_add(counter.complete(root, depth));
+                     nb_formulas
+                  })
+                  .collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use std::collections::HashSet;
+
+   /// sums and products of ones
+   #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+   enum State
+   {
+      Expr,
+      Factor,
+      One,
+      Add,
+      Mul
+   }
+
+   impl Grammar for State
+   {
+      type ScoreType = usize;
+
+      fn root_state() -> Self
+      {
+         State::Expr
+      }
+
+      fn expand(self) -> Vec<Vec<Self>>
+      {
+         match self
+         {
+            State::Expr => vec![vec![State::One],
+                                vec![State::Add, State::Expr, State::Expr],
+                                vec![State::Mul, State::Factor, State::Factor]],
+            State::Factor => vec![vec![State::Add, State::Expr, State::Expr], vec![State::One]],
+            _ => vec![]
+         }
+      }
+
+      fn to_string(formula: &Formula<Self>) -> String
+      {
+         format!("{:?}", formula.iter().collect::<Vec<_>>())
+      }
+
+      fn evaluate(formula: &Formula<Self>) -> Self::ScoreType
+      {
+         formula.len()
+      }
+   }
+
+   /// collects the formulas produced by the depth first enumeration
+   fn enumerate_all(available_depth: usize) -> Vec<Formula<State>>
+   {
+      let mut formulas = Vec::new();
+      enumerate(available_depth, |formula| {
+         formulas.push(formula);
+         true
+      });
+      formulas
+   }
+
+   #[test]
+   fn counts_match_enumerations()
+   {
+      let max_depth = 6;
+      let counts = count_formulas::<State>(max_depth);
+      assert_eq!(counts.len(), max_depth + 1);
+      for (depth, &count) in counts.iter().enumerate()
+      {
+         assert_eq!(count, enumerate_all(depth).len() as u128);
+         assert_eq!(count, Enumeration::<State>::new(depth).count() as u128);
+      }
+   }
+
+   #[test]
+   fn enumerates_distinct_formulas_by_increasing_size()
+   {
+      let depth = 5;
+      let formulas: Vec<Formula<State>> = Enumeration::new(depth).collect();
+      assert!(formulas.windows(2).all(|pair| pair[0].len() <= pair[1].len()));
+      let distinct: HashSet<Formula<State>> = formulas.iter().cloned().collect();
+      assert_eq!(distinct.len(), formulas.len());
+      let expected: HashSet<Formula<State>> = enumerate_all(depth).into_iter().collect();
+      assert!(distinct == expected);
+   }
+
+   #[test]
+   fn stops_when_asked()
+   {
+      let mut nb_visited = 0;
+      let exhausted = enumerate::<State, _>(5, |_| {
+         nb_visited += 1;
+         nb_visited < 3
+      });
+      assert!(!exhausted);
+      assert_eq!(nb_visited, 3);
+   }
+}
@@ -2,18 +2,31 @@ use rand::Rng;
 use crate::distribution::Distribution;
 use crate::grammar::{Grammar, Formula};
 use super::tree::*;
+use super::normalizer::Normalizer;
+use super::widening::Widening;
+use super::context::Context;
+use crate::memory::TreeSize;
 
-/// takes a tree, its prior, a random number generator and the available depth and expand the tree
+/// takes a tree, its prior, the context of the search and the available depth and expand the tree
 /// return the result of the expansion as a (ReturnType, formula, Option<score>)
-pub fn expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
-                                 mut formula: Formula<State>,
-                                 mut stack: Vec<State>,
-                                 rng: &mut RNG,
-                                 available_depth: i64)
-                                 -> (ReturnType<Tree<Distr>>, Formula<State>, State::ScoreType)
+/// NOTE: `tree_size` is kept up to date with the changes done to the children and distributions of the nodes
+///       the nodes visited are stamped with the current iteration of the `context`
+///       the children that can be explored are limited by the widening of the `context`
+///       the rules chosen are recorded in the `context` so that the nodes can update their rule statistics
+///       each decision uses one unit of the available depth, past it only the first rule is explored
+///       (as in `random_expand`, the tree then explores exactly the formulas counted by `count_formulas`)
+pub fn expand<State, Distr, RNG, Norm, Widen>(mut tree: &mut Tree<Distr>,
+                                              mut formula: Formula<State>,
+                                              mut stack: Vec<State>,
+                                              context: &mut Context<State, RNG, Norm, Widen>,
+                                              tree_size: &mut TreeSize,
+                                              available_depth: i64)
+                                              -> (ReturnType<Tree<Distr>>, Formula<State>, State::ScoreType)
    where State: Grammar,
          Distr: Distribution<ScoreType = State::ScoreType>,
-         RNG: Rng
+         RNG: Rng,
+         Norm: Normalizer<State::ScoreType>,
+         Widen: Widening
 {
    match stack.last()
    {
@@ -21,6 +34,7 @@ pub fn expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
       {
          // terminal node, we evaluate the formula and backpropagate
          let score = formula.evaluate();
+         context.normalizer.observe(score);
          (ReturnType::DeleteChild, formula, score)
       }
       Some(&state) =>
@@ -33,14 +47,14 @@ pub fn expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
                // terminal state
                stack.pop();
                formula.push(state);
-               expand(&mut tree, formula, stack, rng, available_depth)
+               expand(&mut tree, formula, stack, context, tree_size, available_depth)
             }
             [rule] =>
             {
                // single rule, we can focus on it
                stack.pop();
                stack.extend(rule);
-               expand(&mut tree, formula, stack, rng, available_depth)
+               expand(&mut tree, formula, stack, context, tree_size, available_depth)
             }
             rules =>
             {
@@ -51,40 +65,87 @@ pub fn expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
                   Tree::Leaf =>
                   {
                      // we expand the leaf and then explore it
-                     let children = (0..rules.len()).map(|_| Tree::Leaf).collect();
-                     let mut new_node = Tree::Node(Box::new(Node { distribution: Distr::new(), children }));
-                     let result = expand(&mut new_node, formula, stack, rng, available_depth - 1);
+                     let children = Children::new(rules.len());
+                     let node = Node { distribution: Distr::new(), children };
+                     let mut new_node = Tree::Node(Box::new(node));
+                     // the new node is accounted for by the father once it is inserted in the tree
+                     let mut new_node_size = TreeSize::of(&new_node);
+                     let result = expand(&mut new_node,
+                                         formula,
+                                         stack,
+                                         context,
+                                         &mut new_node_size,
+                                         available_depth);
                      ReturnType::new_tree(result, new_node)
                   }
                   Tree::KnownLeaf(box distribution) =>
                   {
                      // we expand the leaf and then explore it
-                     let children = (0..rules.len()).map(|_| Tree::Leaf).collect();
-                     let mut new_node =
-                        Tree::Node(Box::new(Node { distribution: distribution.clone(), children }));
-                     let result = expand(&mut new_node, formula, stack, rng, available_depth - 1);
+                     let children = Children::new(rules.len());
+                     let distribution = distribution.clone();
+                     let node = Node { distribution, children };
+                     let mut new_node = Tree::Node(Box::new(node));
+                     // the new node is accounted for by the father once it is inserted in the tree
+                     let mut new_node_size = TreeSize::of(&new_node);
+                     let result = expand(&mut new_node,
+                                         formula,
+                                         stack,
+                                         context,
+                                         &mut new_node_size,
+                                         available_depth);
                      ReturnType::new_tree(result, new_node)
                   }
                   Tree::Node(box Node { ref mut distribution, ref mut children }) =>
                   {
                      // we choose a child using the prior and explore it
-                     let index_best_child = Tree::best_child(children, distribution, rng, available_depth);
+                     distribution.visit(context.iteration);
+                     let nb_visit = distribution.nb_visit();
+                     let nb_children = context.widening.nb_children(nb_visit, children.nb_rules());
+                     let rng = &mut context.rng;
+                     let prior = |rule_index| state.rule_prior(rule_index);
+                     let index_best_child = Tree::best_child_among(children,
+                                                                   nb_children,
+                                                                   prior,
+                                                                   distribution,
+                                                                   rng,
+                                                                   available_depth);
+                     let first_choice = context.choose(state, index_best_child);
                      // update the stack
                      let rule = rules[index_best_child].clone();
                      stack.pop();
                      stack.extend(rule);
-                     // expand the child
-                     let (action, formula, score) =
-                        expand(&mut children[index_best_child], formula, stack, rng, available_depth);
-                     distribution.update(score);
+                     // expand the child (an untried child being a leaf that is not stored yet)
+                     let mut untried_child = Tree::Leaf;
+                     let child = if children.is_untried(index_best_child)
+                     {
+                        &mut untried_child
+                     }
+                     else
+                     {
+                        children.get_mut(index_best_child).expect("Expand: tried to explore a deleted tree!")
+                     };
+                     let (action, formula, score) = expand(child,
+                                                           formula,
+                                                           stack,
+                                                           context,
+                                                           tree_size,
+                                                           available_depth - 1);
+                     // the statistics of the node can own heap memory (see `Distribution::heap_size`)
+                     let old_distribution_size = TreeSize::of_distribution(distribution);
+                     let normalized_score = context.normalizer.normalize(score);
+                     distribution.update(normalized_score);
+                     context.update_rules(distribution, state, first_choice, normalized_score);
+                     let new_distribution_size = TreeSize::of_distribution(distribution);
+                     *tree_size = *tree_size + new_distribution_size - old_distribution_size;
                      match action
                      {
                         ReturnType::DeleteChild =>
                         {
-                           children[index_best_child] = Tree::Deleted;
-                           if children.iter().all(|t| t.is_deleted())
+                           replace_child(children, index_best_child, Tree::Deleted, tree_size);
+                           if children.is_exhausted() || (available_depth <= 0)
                            {
                               // no more children, we can delete this node
+                              // (once the depth is exhausted, only the first child can be explored)
                               (ReturnType::DeleteChild, formula, score)
                            }
                            else
@@ -101,7 +162,7 @@ pub fn expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
                         ReturnType::NewTree(child_tree) =>
                         {
                            // we can replace this child and update its prior
-                           children[index_best_child] = child_tree;
+                           replace_child(children, index_best_child, child_tree, tree_size);
                            (ReturnType::DoNothing, formula, score)
                         }
                      }
@@ -112,3 +173,195 @@ pub fn expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
       }
    }
 }
+
+/// replaces the child associated with a rule and updates the size of the tree accordingly
+/// NOTE: the size includes the storage of the children which can grow or shrink
+fn replace_child<Distr: Distribution>(children: &mut Children<Distr>,
+                                      rule_index: usize,
+                                      new_child: Tree<Distr>,
+                                      tree_size: &mut TreeSize)
+{
+   let old_size = TreeSize::of_children(children);
+   let old_child_size = children.get(rule_index).map(TreeSize::of).unwrap_or_default();
+   children.replace(rule_index, new_child);
+   let new_size = TreeSize::of_children(children);
+   let new_child_size = children.get(rule_index).map(TreeSize::of).unwrap_or_default();
+   *tree_size = *tree_size + new_size + new_child_size - old_size - old_child_size;
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+   use std::collections::HashSet;
+   use crate::distribution::{Rave, UcbTuned, RandomSearch};
+   use crate::search::exhaustive::count_formulas;
+   use crate::search::normalizer::NoNormalization;
+   use crate::search::widening::{NoWidening, ProgressiveWidening};
+   use crate::search::test_grammar::Sum;
+
+   #[test]
+   fn explores_the_formulas_counted_within_the_depth()
+   {
+      let max_depth = 4;
+      for (depth, nb_formulas) in count_formulas::<Sum>(max_depth).into_iter().enumerate()
+      {
+         let rng = Xoshiro256Plus::seed_from_u64(0);
+         let mut context = Context::new(rng, NoNormalization {}, NoWidening);
+         let mut tree = Tree::<UcbTuned>::new();
+         let mut tree_size = TreeSize::of(&tree);
+         let mut formulas = HashSet::new();
+         let mut nb_iterations = 0;
+         loop
+         {
+            context.start_iteration(nb_iterations);
+            nb_iterations += 1;
+            let (action, formula, _) = expand(&mut tree,
+                                              Formula::empty(),
+                                              vec![Sum::root_state()],
+                                              &mut context,
+                                              &mut tree_size,
+                                              depth as i64);
+            formulas.insert(formula);
+            match action
+            {
+               ReturnType::NewTree(new_tree) =>
+               {
+                  tree_size.replace(&tree, &new_tree);
+                  tree = new_tree
+               }
+               ReturnType::DeleteChild => break,
+               ReturnType::DoNothing => ()
+            }
+         }
+         // every formula is evaluated once before the space is exhausted
+         assert_eq!(formulas.len() as u128, nb_formulas);
+         assert_eq!(nb_iterations as u128, nb_formulas);
+      }
+   }
+
+   /// a digit followed by bits, the larger digits having a higher prior
+   #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+   enum Wide
+   {
+      Root,
+      Digit(u8),
+      Bits(u8)
+   }
+
+   impl Grammar for Wide
+   {
+      type ScoreType = f64;
+
+      fn root_state() -> Self
+      {
+         Wide::Root
+      }
+
+      fn expand(self) -> Vec<Vec<Self>>
+      {
+         match self
+         {
+            Wide::Root => (0..8).map(|digit| vec![Wide::Digit(digit), Wide::Bits(8)]).collect(),
+            Wide::Bits(0) | Wide::Digit(_) => vec![],
+            Wide::Bits(n) => (0..2).map(|bit| vec![Wide::Digit(bit), Wide::Bits(n - 1)]).collect()
+         }
+      }
+
+      fn rule_prior(self, rule_index: usize) -> f64
+      {
+         rule_index as f64
+      }
+
+      fn to_string(formula: &Formula<Self>) -> String
+      {
+         format!("{:?}", formula.iter().collect::<Vec<_>>())
+      }
+
+      fn evaluate(_formula: &Formula<Self>) -> Self::ScoreType
+      {
+         0.
+      }
+   }
+
+   #[test]
+   fn the_widening_unlocks_the_rules_with_the_highest_prior()
+   {
+      let rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut context = Context::new(rng, NoNormalization {}, ProgressiveWidening { k: 1., alpha: 0.5 });
+      let mut tree = Tree::<RandomSearch>::new();
+      let mut tree_size = TreeSize::of(&tree);
+      for nb_iterations in 1..=40
+      {
+         context.start_iteration(nb_iterations - 1);
+         let (action, _, _) =
+            expand(&mut tree, Formula::empty(), vec![Wide::root_state()], &mut context, &mut tree_size, 20);
+         match action
+         {
+            ReturnType::NewTree(new_tree) =>
+            {
+               tree_size.replace(&tree, &new_tree);
+               tree = new_tree
+            }
+            ReturnType::DeleteChild => panic!("the search space should not be exhausted"),
+            ReturnType::DoNothing => ()
+         }
+         // the last choice was made after nb_iterations-1 visits, with ceil(k*visits^alpha) unlocked rules
+         let nb_unlocked = (((nb_iterations - 1) as f64).sqrt().ceil() as usize).clamp(1, 8);
+         match &tree
+         {
+            Tree::Node(box node) =>
+            {
+               assert_eq!(node.distribution.nb_visit(), nb_iterations as u64);
+               let opened: Vec<usize> = node.children.visited().map(|(i, _)| i).collect();
+               assert_eq!(opened, (8 - nb_unlocked..8).collect::<Vec<_>>());
+            }
+            _ => panic!("the root should be a node")
+         }
+      }
+   }
+
+   #[test]
+   fn rave_counts_every_use_of_a_rule_in_the_subtree()
+   {
+      let rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut context = Context::new(rng, NoNormalization {}, NoWidening);
+      let mut tree = Tree::<Rave<UcbTuned>>::new();
+      let mut tree_size = TreeSize::of(&tree);
+      let (mut nb_ones, mut nb_adds) = (0, 0);
+      for iteration in 0..200
+      {
+         context.start_iteration(iteration);
+         let (action, formula, _) =
+            expand(&mut tree, Formula::empty(), vec![Sum::root_state()], &mut context, &mut tree_size, 10);
+         nb_ones += formula.iter().filter(|&&state| state == Sum::One).count() as u64;
+         nb_adds += formula.iter().filter(|&&state| state == Sum::Add).count() as u64;
+         match action
+         {
+            ReturnType::NewTree(new_tree) =>
+            {
+               tree_size.replace(&tree, &new_tree);
+               tree = new_tree
+            }
+            ReturnType::DeleteChild => panic!("the search space should not be exhausted"),
+            ReturnType::DoNothing => ()
+         }
+      }
+      // the amaf statistics are counted in the size of the tree
+      assert!(tree_size.nb_heap_bytes > 0);
+      assert_eq!(tree_size, TreeSize::of(&tree));
+      // every expression is expanded inside the subtree of the root
+      match tree
+      {
+         Tree::Node(box node) =>
+         {
+            assert_eq!(node.distribution.nb_visit(), 200);
+            assert_eq!(node.distribution.nb_rule_visit(0), nb_ones);
+            assert_eq!(node.distribution.nb_rule_visit(1), nb_adds);
+         }
+         _ => panic!("the root should be a node")
+      }
+   }
+}
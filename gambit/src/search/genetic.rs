@@ -0,0 +1,486 @@
+use rand::Rng;
+use crate::grammar::{Grammar, Formula};
+use crate::result::Result;
+
+//-----------------------------------------------------------------------------
+// TYPES
+
+/// the parameters of a genetic programming search
+pub struct GeneticParameters
+{
+   pub tournament_size: usize, // number of individuals competing to be selected as a parent
+   pub crossover_rate: f64,    // probability of producing a child with a crossover (instead of a mutation)
+   pub bloat_factor: i64       // max decisions of a child, relative to the largest initial individual
+}
+
+impl Default for GeneticParameters
+{
+   fn default() -> Self
+   {
+      GeneticParameters { tournament_size: 3, crossover_rate: 0.9, bloat_factor: 2 }
+   }
+}
+
+/// a derivation tree: a state and the states produced by the rule applied to it
+/// NOTE: the number of nodes and decisions of the tree are cached in its root
+///       such that a subtree can be found from its index without walking the whole tree
+#[derive(Clone)]
+pub struct Derivation<State: Grammar>
+{
+   state: State,
+   nb_rules: usize,                 // 0 for a terminal state, more than 1 for a decision
+   nb_nodes: usize,                 // number of nodes in the derivation tree
+   nb_decisions: i64,               // number of decisions taken in the derivation tree
+   children: Vec<Derivation<State>> // one child per state of the rule applied
+}
+
+/// a derivation tree and the score of its formula
+#[derive(Clone)]
+pub struct Individual<State: Grammar>
+{
+   derivation: Derivation<State>,
+   score: State::ScoreType
+}
+
+//-----------------------------------------------------------------------------
+// DERIVATION
+
+impl<State: Grammar> Derivation<State>
+{
+   /// builds a derivation tree from a state, its number of rules and the derivation trees of its children
+   fn new(state: State, nb_rules: usize, children: Vec<Derivation<State>>) -> Derivation<State>
+   {
+      let decision = if nb_rules > 1 { 1 } else { 0 };
+      let nb_nodes = 1 + children.iter().map(|child| child.nb_nodes).sum::<usize>();
+      let nb_decisions = decision + children.iter().map(|child| child.nb_decisions).sum::<i64>();
+      Derivation { state, nb_rules, nb_nodes, nb_decisions, children }
+   }
+
+   /// builds a random derivation tree from a state
+   /// follows the same conventions as `random_expand`: each decision uses one unit of the available depth
+   /// and, once it is exhausted, the first rule is always picked
+   pub fn grow<RNG: Rng>(state: State, available_depth: &mut i64, rng: &mut RNG) -> Derivation<State>
+   {
+      let mut rules = state.expand();
+      let nb_rules = rules.len();
+      let rule = match nb_rules
+      {
+         0 => return Derivation::new(state, nb_rules, Vec::new()),
+         1 => rules.pop().unwrap(),
+         _ if *available_depth <= 0 =>
+         {
+            *available_depth -= 1;
+            rules.swap_remove(0)
+         }
+         _ =>
+         {
+            *available_depth -= 1;
+            rules.swap_remove(rng.gen_range(0, nb_rules))
+         }
+      };
+      // the states are expanded in the same order as with a stack: last state first
+      let mut children: Vec<Derivation<State>> =
+         rule.iter().rev().map(|&child_state| Derivation::grow(child_state, available_depth, rng)).collect();
+      children.reverse();
+      Derivation::new(state, nb_rules, children)
+   }
+
+   /// returns the formula represented by the derivation tree
+   pub fn to_formula(&self) -> Formula<State>
+   {
+      /// pushes the terminal states in the same order as `random_expand`
+      fn push_terminals<State: Grammar>(derivation: &Derivation<State>, formula: &mut Formula<State>)
+      {
+         if derivation.nb_rules == 0
+         {
+            formula.push(derivation.state);
+         }
+         for child in derivation.children.iter().rev()
+         {
+            push_terminals(child, formula);
+         }
+      }
+      let mut formula = Formula::empty();
+      push_terminals(self, &mut formula);
+      formula
+   }
+
+   /// returns the position of the child that contains the node at the given index
+   /// the index, given relative to the first child, is made relative to that child
+   fn child_position(&self, index: &mut usize) -> usize
+   {
+      for (position, child) in self.children.iter().enumerate()
+      {
+         if *index < child.nb_nodes
+         {
+            return position;
+         }
+         *index -= child.nb_nodes;
+      }
+      panic!("Derivation: index out of the tree.")
+   }
+
+   /// returns the subtree at the given index (in depth first order)
+   fn subtree(&self, mut index: usize) -> &Derivation<State>
+   {
+      let mut derivation = self;
+      while index != 0
+      {
+         index -= 1;
+         let position = derivation.child_position(&mut index);
+         derivation = &derivation.children[position];
+      }
+      derivation
+   }
+
+   /// replaces the subtree at the given index (in depth first order), returns the previous subtree
+   fn replace(&mut self, index: usize, subtree: Derivation<State>) -> Derivation<State>
+   {
+      if index == 0
+      {
+         return std::mem::replace(self, subtree);
+      }
+      let mut child_index = index - 1;
+      let position = self.child_position(&mut child_index);
+      let child = &mut self.children[position];
+      let (nb_nodes, nb_decisions) = (child.nb_nodes, child.nb_decisions);
+      let previous = child.replace(child_index, subtree);
+      self.nb_nodes = self.nb_nodes + child.nb_nodes - nb_nodes;
+      self.nb_decisions += child.nb_decisions - nb_decisions;
+      previous
+   }
+
+   /// returns the indexes of all the non terminal nodes with the given state (or any state if None)
+   fn non_terminal_indexes(&self, state: Option<State>) -> Vec<usize>
+   {
+      fn collect<State: Grammar>(derivation: &Derivation<State>,
+                                 state: Option<State>,
+                                 index: &mut usize,
+                                 indexes: &mut Vec<usize>)
+      {
+         let matches_state = state.is_none_or(|state| state == derivation.state);
+         if (derivation.nb_rules > 0) && matches_state
+         {
+            indexes.push(*index);
+         }
+         *index += 1;
+         for child in derivation.children.iter()
+         {
+            collect(child, state, index, indexes);
+         }
+      }
+      let mut indexes = Vec::new();
+      collect(self, state, &mut 0, &mut indexes);
+      indexes
+   }
+}
+
+//-----------------------------------------------------------------------------
+// OPERATORS
+
+/// replaces a random subtree of the father with a subtree of the mother that starts with the same state
+/// returns None if the mother has no compatible subtree or if the child would have more than max_nb_decisions
+fn crossover<State, RNG>(father: &Derivation<State>,
+                         mother: &Derivation<State>,
+                         max_nb_decisions: i64,
+                         rng: &mut RNG)
+                         -> Option<Derivation<State>>
+   where State: Grammar,
+         RNG: Rng
+{
+   let father_indexes = father.non_terminal_indexes(None);
+   if father_indexes.is_empty()
+   {
+      return None;
+   }
+   let father_index = father_indexes[rng.gen_range(0, father_indexes.len())];
+   let father_subtree = father.subtree(father_index);
+   let mother_indexes = mother.non_terminal_indexes(Some(father_subtree.state));
+   if mother_indexes.is_empty()
+   {
+      return None;
+   }
+   let mother_index = mother_indexes[rng.gen_range(0, mother_indexes.len())];
+   let mother_subtree = mother.subtree(mother_index);
+   // checks the size of the child before building it
+   let nb_decisions = father.nb_decisions - father_subtree.nb_decisions + mother_subtree.nb_decisions;
+   if nb_decisions > max_nb_decisions
+   {
+      return None;
+   }
+   let mut child = father.clone();
+   child.replace(father_index, mother_subtree.clone());
+   Some(child)
+}
+
+/// replaces a random subtree of the father with a new random subtree
+/// the new subtree can use the depth that is not used by the rest of the tree
+/// returns None if the father has no non terminal node
+fn mutation<State, RNG>(father: &Derivation<State>, available_depth: i64, rng: &mut RNG)
+                        -> Option<Derivation<State>>
+   where State: Grammar,
+         RNG: Rng
+{
+   let indexes = father.non_terminal_indexes(None);
+   if indexes.is_empty()
+   {
+      return None;
+   }
+   let index = indexes[rng.gen_range(0, indexes.len())];
+   let subtree = father.subtree(index);
+   let mut subtree_depth = available_depth - (father.nb_decisions - subtree.nb_decisions);
+   let new_subtree = Derivation::grow(subtree.state, &mut subtree_depth, rng);
+   let mut child = father.clone();
+   child.replace(index, new_subtree);
+   Some(child)
+}
+
+/// returns the best of tournament_size individuals picked at random
+fn tournament<'a, State, RNG>(population: &'a [Individual<State>],
+                              tournament_size: usize,
+                              rng: &mut RNG)
+                              -> &'a Individual<State>
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         RNG: Rng
+{
+   let mut winner = &population[rng.gen_range(0, population.len())];
+   for _ in 1..tournament_size
+   {
+      let challenger = &population[rng.gen_range(0, population.len())];
+      if challenger.score > winner.score
+      {
+         winner = challenger;
+      }
+   }
+   winner
+}
+
+//-----------------------------------------------------------------------------
+// EVOLUTION
+
+/// evaluates a derivation tree and updates the result
+fn evaluate<State, Res>(derivation: Derivation<State>, result: &mut Res) -> Individual<State>
+   where State: Grammar,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   let formula = derivation.to_formula();
+   let score = formula.evaluate();
+   result.update(formula, score);
+   Individual { derivation, score }
+}
+
+/// evolves a population of derivation trees until there is no evaluation left
+/// each generation keeps its best individual and fills the rest of the population with children
+/// produced by subtree crossovers or mutations of parents picked by tournament
+/// returns the number of generations
+/// NOTE: the search stops early if a full generation is produced without any valid child
+pub fn evolve<State, RNG, Res>(available_depth: usize,
+                               population_size: usize,
+                               parameters: &GeneticParameters,
+                               rng: &mut RNG,
+                               result: &mut Res,
+                               nb_evaluations_left: &mut usize)
+                               -> usize
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         RNG: Rng,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   let available_depth = available_depth as i64;
+
+   // random initial population
+   let mut population = Vec::with_capacity(population_size);
+   while (population.len() < population_size) && (*nb_evaluations_left > 0)
+   {
+      *nb_evaluations_left -= 1;
+      let mut depth = available_depth;
+      let derivation = Derivation::grow(State::root_state(), &mut depth, rng);
+      population.push(evaluate(derivation, result));
+   }
+
+   // the forced decisions taken once the depth is exhausted make the size of an individual hard to predict
+   let max_initial_nb_decisions =
+      population.iter().map(|individual| individual.derivation.nb_decisions).max().unwrap_or(0);
+   let max_nb_decisions = parameters.bloat_factor * max_initial_nb_decisions;
+
+   let mut nb_generations = 0;
+   while (*nb_evaluations_left > 0) && !population.is_empty()
+   {
+      let nb_evaluations_start = *nb_evaluations_left;
+      // elitism
+      let best = population.iter().fold(&population[0], |best, individual| {
+                                     if individual.score > best.score { individual } else { best }
+                                  });
+      let mut children = vec![best.clone()];
+      while (children.len() < population_size) && (*nb_evaluations_left > 0)
+      {
+         let father = tournament(&population, parameters.tournament_size, rng);
+         let child = if rng.gen::<f64>() < parameters.crossover_rate
+         {
+            // falls back to a mutation if the parents cannot produce a valid child
+            let mother = tournament(&population, parameters.tournament_size, rng);
+            crossover(&father.derivation, &mother.derivation, max_nb_decisions, rng)
+               .or_else(|| mutation(&father.derivation, available_depth, rng))
+         }
+         else
+         {
+            mutation(&father.derivation, available_depth, rng)
+         };
+         match child
+         {
+            Some(child) if child.nb_decisions <= max_nb_decisions =>
+            {
+               *nb_evaluations_left -= 1;
+               children.push(evaluate(child, result));
+            }
+            _ =>
+            {
+               // no valid child, the father is copied into the next generation
+               children.push(father.clone());
+            }
+         }
+      }
+      population = children;
+      nb_generations += 1;
+      if *nb_evaluations_left == nb_evaluations_start
+      {
+         // no valid child could be produced, the population cannot evolve anymore
+         break;
+      }
+   }
+   nb_generations
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+   use crate::result::Single;
+   use super::super::test_grammar::Bits;
+
+   /// sums of variables
+   #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+   enum State
+   {
+      Expr,
+      Add,
+      X,
+      Y
+   }
+
+   impl Grammar for State
+   {
+      type ScoreType = f64;
+
+      fn root_state() -> Self
+      {
+         State::Expr
+      }
+
+      fn expand(self) -> Vec<Vec<Self>>
+      {
+         match self
+         {
+            State::Expr => vec![vec![State::X], vec![State::Y], vec![State::Add, State::Expr, State::Expr]],
+            _ => vec![]
+         }
+      }
+
+      fn to_string(formula: &Formula<Self>) -> String
+      {
+         format!("{:?}", formula.iter().collect::<Vec<_>>())
+      }
+
+      fn evaluate(formula: &Formula<Self>) -> Self::ScoreType
+      {
+         formula.len() as f64
+      }
+   }
+
+   /// returns true if every node of the derivation applies one of the rules of its state
+   /// and if the cached number of nodes and decisions are correct
+   fn is_valid<State: Grammar>(derivation: &Derivation<State>) -> bool
+   {
+      let rules = derivation.state.expand();
+      let children_states: Vec<State> = derivation.children.iter().map(|child| child.state).collect();
+      let applies_a_rule =
+         if rules.is_empty() { children_states.is_empty() } else { rules.contains(&children_states) };
+      let recomputed = Derivation::new(derivation.state, derivation.nb_rules, derivation.children.clone());
+      applies_a_rule
+      && (derivation.nb_rules == rules.len())
+      && (derivation.nb_nodes == recomputed.nb_nodes)
+      && (derivation.nb_decisions == recomputed.nb_decisions)
+      && derivation.children.iter().all(is_valid)
+   }
+
+   /// returns the states of the derivation in depth first order
+   fn states(derivation: &Derivation<State>) -> Vec<State>
+   {
+      let mut states = vec![derivation.state];
+      for child in derivation.children.iter()
+      {
+         states.extend(self::states(child));
+      }
+      states
+   }
+
+   #[test]
+   fn finds_and_replaces_subtrees_by_index()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut derivation = Derivation::grow(State::Expr, &mut 6, &mut rng);
+      let states = states(&derivation);
+      assert_eq!(states.len(), derivation.nb_nodes);
+      for (index, &state) in states.iter().enumerate()
+      {
+         assert!(derivation.subtree(index).state == state);
+      }
+      // replaces the last expression with a single variable
+      let index = states.iter().rposition(|&state| state == State::Expr).unwrap();
+      let leaf = Derivation::new(State::Expr, 3, vec![Derivation::new(State::X, 0, Vec::new())]);
+      let previous = derivation.replace(index, leaf);
+      assert!(is_valid(&derivation));
+      assert_eq!(derivation.nb_nodes, states.len() - previous.nb_nodes + 2);
+      assert!(derivation.subtree(index + 1).state == State::X);
+   }
+
+   #[test]
+   fn crossover_and_mutation_keep_derivations_valid()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let available_depth = 6;
+      let max_nb_decisions = 12;
+      for _ in 0..1000
+      {
+         let father = Derivation::grow(State::Expr, &mut available_depth.clone(), &mut rng);
+         let mother = Derivation::grow(State::Expr, &mut available_depth.clone(), &mut rng);
+         assert!(is_valid(&father) && is_valid(&mother));
+         if let Some(child) = crossover(&father, &mother, max_nb_decisions, &mut rng)
+         {
+            assert!(is_valid(&child));
+            assert!(child.nb_decisions <= max_nb_decisions);
+         }
+         let child = mutation(&father, available_depth, &mut rng).unwrap();
+         assert!(is_valid(&child));
+         // the formula contains the terminal states of the derivation
+         let nb_terminals = states(&child).iter().filter(|&&state| state != State::Expr).count();
+         assert_eq!(child.to_formula().len(), nb_terminals);
+      }
+   }
+
+   #[test]
+   fn evolves_until_there_is_no_evaluation_left()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut result = Single::<Bits>::new();
+      let mut nb_evaluations_left = 1000;
+      let parameters = GeneticParameters { tournament_size: 5, crossover_rate: 0.5, bloat_factor: 1 };
+      let nb_generations = evolve(100, 20, &parameters, &mut rng, &mut result, &mut nb_evaluations_left);
+      assert_eq!(nb_evaluations_left, 0);
+      assert!(nb_generations > 1);
+      assert_eq!(result.score, 8.);
+   }
+}
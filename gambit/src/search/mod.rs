@@ -2,18 +2,56 @@ mod tree;
 mod expand;
 mod no_expand;
 mod random_expand;
+mod context;
+mod normalizer;
+mod widening;
+mod observer;
+mod pruning;
+mod position;
+mod nmcs;
+mod nrpa;
+mod beam;
+mod genetic;
+mod portfolio;
+mod exhaustive;
+mod sampler;
+mod rollout;
+#[cfg(test)]
+pub(crate) mod test_grammar;
 
 use rand::FromEntropy; // for random initialisation
 use rand::SeedableRng;
+use rand::Rng;
 use rand_xoshiro::Xoshiro256Plus;
-use crate::distribution::Distribution;
+use crate::distribution::{Distribution, Rave, ThompsonMax, UcbTuned, RandomSearch, Timestamped};
 use crate::grammar::{Grammar, Formula};
 use crate::result::Result;
-use crate::memory::{MemoryTracker, memory_summary, memory_used};
+use crate::memory::{MemoryTracker, MemoryBudget, TreeSize, log_memory_summary};
+use log::info;
 use tree::*;
-pub use tree::{Node, Tree};
+pub use tree::{Node, Tree, Children};
+pub use pruning::{Pruning, PruneLeastRecentlyVisited, PruneLowestScore, CollapseLeastRecentlyVisited};
+pub use normalizer::{Normalizer, NoNormalization, MinMaxNormalization, RankNormalization};
+pub use widening::{Widening, NoWidening, ProgressiveWidening};
+pub use observer::{Observer, NoObserver};
+pub use beam::Heuristic;
+pub use portfolio::{Portfolio, Arm};
+pub use exhaustive::{Enumeration, count_formulas};
+pub use sampler::{SizeSampler, UniformRollout};
+pub use rollout::{Rollout, RandomRollout};
+use observer::{update_result, report_exhaustion};
 use expand::expand;
 use no_expand::*;
+use context::Context;
+pub use context::MctsParameters;
+use position::Position;
+use nmcs::nested_rollout;
+use nrpa::{nrpa, Policy};
+pub use nrpa::NrpaParameters;
+use beam::{beam, rollout_estimate};
+use genetic::evolve;
+pub use genetic::GeneticParameters;
+use exhaustive::enumerate;
 
 //-----------------------------------------------------------------------------
 // SEARCH
@@ -24,29 +62,98 @@ pub fn search<State, Distr, Res>(available_depth: usize, nb_iterations: usize) -
    where State: Grammar,
          Distr: Distribution<ScoreType = State::ScoreType>,
          Res: Result<State, ScoreType = State::ScoreType>
+{
+   search_normalized::<State, Distr, Res, NoNormalization>(available_depth, nb_iterations)
+}
+
+/// performs the search for a given number of iterations
+/// NOTE: the scores are normalized before being given to the distributions
+/// WARNING: this function is memory hungry and could fill the RAM
+pub fn search_normalized<State, Distr, Res, Norm>(available_depth: usize, nb_iterations: usize) -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Norm: Normalizer<State::ScoreType>
+{
+   search_observed::<State, Distr, Res, Norm, NoWidening, NoObserver>(available_depth,
+                                                                      nb_iterations,
+                                                                      MctsParameters::default(),
+                                                                      &mut NoObserver)
+}
+
+/// performs the search for a given number of iterations
+/// NOTE: the parameters control the number of children explored in each node (see `MctsParameters`)
+/// WARNING: this function is memory hungry and could fill the RAM
+pub fn search_parameterized<State, Distr, Res, Widen>(available_depth: usize,
+                                                      nb_iterations: usize,
+                                                      parameters: MctsParameters<Widen>)
+                                                      -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Widen: Widening
+{
+   search_observed::<State, Distr, Res, NoNormalization, Widen, NoObserver>(available_depth,
+                                                                            nb_iterations,
+                                                                            parameters,
+                                                                            &mut NoObserver)
+}
+
+/// performs the search for a given number of iterations
+/// NOTE: the scores are normalized before being given to the distributions
+///       the parameters control the number of children explored in each node
+///       the observer is notified of the progress of the search and can stop it
+/// WARNING: this function is memory hungry and could fill the RAM
+pub fn search_observed<State, Distr, Res, Norm, Widen, Obs>(available_depth: usize,
+                                                            nb_iterations: usize,
+                                                            parameters: MctsParameters<Widen>,
+                                                            observer: &mut Obs)
+                                                            -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Norm: Normalizer<State::ScoreType>,
+         Widen: Widening,
+         Obs: Observer<State>
 {
    let memory_tracker = MemoryTracker::new();
 
    //let mut rng = Xoshiro256Plus::seed_from_u64(0);
-   let mut rng = Xoshiro256Plus::from_entropy();
+   let rng = Xoshiro256Plus::from_entropy();
+   let mut context = Context::new(rng, Norm::new(), parameters.widening);
    let mut tree = Tree::<Distr>::new();
+   let mut tree_size = TreeSize::of(&tree);
    let mut result = Res::new();
-   for _ in 0..nb_iterations
+   for iteration in 0..nb_iterations
    {
+      if !observer.iteration_start(iteration)
+      {
+         break;
+      }
+      context.start_iteration(iteration);
       let formula = Formula::empty();
       let stack = vec![State::root_state()];
-      let (action, formula, score) = expand(&mut tree, formula, stack, &mut rng, available_depth as i64);
-      result.update(formula, score);
+      let (action, formula, score) =
+         expand(&mut tree, formula, stack, &mut context, &mut tree_size, available_depth as i64);
+      update_result(&mut result, observer, iteration, formula, score);
       match action
       {
-         ReturnType::NewTree(updated_tree) => tree = updated_tree,
-         ReturnType::DeleteChild => break,
+         ReturnType::NewTree(updated_tree) =>
+         {
+            tree_size.replace(&tree, &updated_tree);
+            tree = updated_tree
+         }
+         ReturnType::DeleteChild =>
+         {
+            report_exhaustion(&mut result, observer, iteration, iteration + 1);
+            break;
+         }
          ReturnType::DoNothing => ()
       }
    }
 
-   memory_summary(&tree);
-   memory_tracker.print_memory_usage();
+   log_memory_summary(&tree);
+   memory_tracker.log_memory_usage();
    result
 }
 
@@ -65,6 +172,18 @@ pub fn search_optional<State, Distr, Res>(available_depth: usize, nb_iterations:
    result.get_result()
 }
 
+/// performs the search for a given number of iterations
+/// NOTE: uses all-moves-as-first (RAVE) statistics to speed up the learning of wide rules
+///       `Rave` can be used with any other search taking a distribution
+/// WARNING: this function is memory hungry and could fill the RAM
+pub fn search_rave<State, Distr, Res>(available_depth: usize, nb_iterations: usize) -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   search::<State, Rave<Distr>, Res>(available_depth, nb_iterations)
+}
+
 /// performs the search for a given number of iterations
 /// NOTE: change searching strategy once the available RAM drops below the given level
 ///       this function can run forever without crashing the computeur
@@ -76,38 +195,161 @@ pub fn memory_limited_search<State, Distr, Res>(available_depth: usize,
          Distr: Distribution<ScoreType = State::ScoreType>,
          Res: Result<State, ScoreType = State::ScoreType>
 {
-   let free_memory_size: i64 = free_memory_size as i64;
+   memory_limited_search_normalized::<State, Distr, Res, NoNormalization>(available_depth,
+                                                                          nb_iterations,
+                                                                          free_memory_size)
+}
+
+/// performs the search for a given number of iterations
+/// NOTE: change searching strategy once the available RAM drops below the given level
+///       the scores are normalized before being given to the distributions
+pub fn memory_limited_search_normalized<State, Distr, Res, Norm>(available_depth: usize,
+                                                                 nb_iterations: usize,
+                                                                 free_memory_size: usize)
+                                                                 -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Norm: Normalizer<State::ScoreType>
+{
+   let budget = MemoryBudget::FreeMemory(free_memory_size);
+   memory_limited_search_observed::<State,
+                                    Distr,
+                                    Res,
+                                    Norm,
+                                    NoWidening,
+                                    NoObserver>(available_depth,
+                                                nb_iterations,
+                                                budget,
+                                                MctsParameters::default(),
+                                                &mut NoObserver)
+}
+
+/// performs the search for a given number of iterations
+/// NOTE: change searching strategy once the tree has used the given budget
+///       node and byte budgets do not depend on the other processes running on the computer
+pub fn memory_budgeted_search<State, Distr, Res>(available_depth: usize,
+                                                 nb_iterations: usize,
+                                                 budget: MemoryBudget)
+                                                 -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   memory_limited_search_observed::<State,
+                                    Distr,
+                                    Res,
+                                    NoNormalization,
+                                    NoWidening,
+                                    NoObserver>(available_depth,
+                                                nb_iterations,
+                                                budget,
+                                                MctsParameters::default(),
+                                                &mut NoObserver)
+}
+
+/// performs the search for a given number of iterations
+/// NOTE: change searching strategy once the available RAM drops below the given level
+///       the parameters control the number of children explored in each node (see `MctsParameters`)
+pub fn memory_limited_search_parameterized<State, Distr, Res, Widen>(available_depth: usize,
+                                                                     nb_iterations: usize,
+                                                                     free_memory_size: usize,
+                                                                     parameters: MctsParameters<Widen>)
+                                                                     -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Widen: Widening
+{
+   let budget = MemoryBudget::FreeMemory(free_memory_size);
+   memory_limited_search_observed::<State,
+                                    Distr,
+                                    Res,
+                                    NoNormalization,
+                                    Widen,
+                                    NoObserver>(available_depth,
+                                                nb_iterations,
+                                                budget,
+                                                parameters,
+                                                &mut NoObserver)
+}
+
+/// performs the search for a given number of iterations
+/// NOTE: change searching strategy once the tree has used the given budget
+///       the scores are normalized before being given to the distributions
+///       the parameters control the number of children explored in each node
+///       the observer is notified of the progress of the search and can stop it
+pub fn memory_limited_search_observed<State, Distr, Res, Norm, Widen, Obs>(available_depth: usize,
+                                                                           nb_iterations: usize,
+                                                                           budget: MemoryBudget,
+                                                                           parameters: MctsParameters<Widen>,
+                                                                           observer: &mut Obs)
+                                                                           -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Norm: Normalizer<State::ScoreType>,
+         Widen: Widening,
+         Obs: Observer<State>
+{
    let memory_tracker = MemoryTracker::new();
 
-   let mut rng = Xoshiro256Plus::seed_from_u64(0); //from_entropy();
+   let rng = Xoshiro256Plus::seed_from_u64(0); //from_entropy();
+   let mut context = Context::new(rng, Norm::new(), parameters.widening);
    let mut tree = Tree::<Distr>::new();
+   let mut tree_size = TreeSize::of(&tree);
    let mut result = Res::new();
 
    // searches while there is memory available
-   // uses a simple linear model to avoid measuring memory at each iteration
+   // the size of the tree is tracked at each iteration
+   // but the free memory uses a simple linear model to avoid measuring memory at each iteration
+   let system_dependent = budget.is_system_dependent();
    let mut iteration = 0;
    let mut iteration_previous = 0;
-   let mut free_memory_current = memory_tracker.free_memory() as i64;
+   let mut free_memory_current =
+      if system_dependent { memory_tracker.free_memory() as i64 } else { std::i64::MAX };
    let mut free_memory_previous = free_memory_current;
    let mut memory_growth = 0.; // by how much does the memory growth per iteration
    let step_size = 1000; // refresh memory measure every step_size iterations
-   while (iteration < nb_iterations) && (free_memory_current > free_memory_size)
+   let mut stopped = false;
+   while (iteration < nb_iterations) && !budget.is_exhausted(&tree_size, free_memory_current)
    {
+      if !observer.iteration_start(iteration)
+      {
+         stopped = true;
+         break;
+      }
+      context.start_iteration(iteration);
       let formula = Formula::empty();
       let stack = vec![State::root_state()];
-      let (action, formula, score) = expand(&mut tree, formula, stack, &mut rng, available_depth as i64);
-      result.update(formula, score);
+      let (action, formula, score) =
+         expand(&mut tree, formula, stack, &mut context, &mut tree_size, available_depth as i64);
+      update_result(&mut result, observer, iteration, formula, score);
       match action
       {
-         ReturnType::NewTree(updated_tree) => tree = updated_tree,
-         ReturnType::DeleteChild => break,
+         ReturnType::NewTree(updated_tree) =>
+         {
+            tree_size.replace(&tree, &updated_tree);
+            tree = updated_tree
+         }
+         ReturnType::DeleteChild =>
+         {
+            report_exhaustion(&mut result, observer, iteration, iteration + 1);
+            stopped = true;
+            break;
+         }
          ReturnType::DoNothing => ()
       }
       // updates iteration and free_memory_current
       iteration += 1;
+      if !system_dependent
+      {
+         continue;
+      }
       free_memory_current =
          free_memory_previous + (((iteration - iteration_previous) as f64) * memory_growth) as i64;
-      if ((iteration - iteration_previous) % step_size == 0) || (free_memory_current < free_memory_size)
+      if ((iteration - iteration_previous) % step_size == 0)
+         || budget.is_exhausted(&tree_size, free_memory_current)
       {
          free_memory_current = memory_tracker.free_memory() as i64;
          memory_growth = ((free_memory_current as f64) - (free_memory_previous as f64))
@@ -117,27 +359,48 @@ pub fn memory_limited_search<State, Distr, Res>(available_depth: usize,
       }
    }
 
+   if stopped
+   {
+      log_memory_summary(&tree);
+      memory_tracker.log_memory_usage();
+      return result;
+   }
+
    // searches that avoids growing the memory
    let balance_factor = tree.balance_factor(iteration);
-   println!("memory limits reached at iteration n°{}, balance factor is {}, free memory is {}Mo",
-            iteration, balance_factor, free_memory_current);
-   for _ in iteration..nb_iterations
+   info!(target: "gambit::search",
+         iteration = iteration,
+         balance_factor = balance_factor,
+         nb_nodes = tree_size.nb_nodes,
+         tree_bytes = tree_size.nb_bytes;
+         "memory limits reached");
+   observer.memory_mode_switched(iteration, &tree_size);
+   for iteration in iteration..nb_iterations
    {
+      if !observer.iteration_start(iteration)
+      {
+         break;
+      }
+      context.start_iteration(iteration);
       let formula = Formula::empty();
       let stack = vec![State::root_state()];
       let (action, formula, score) =
-         no_expand(&mut tree, formula, stack, &mut rng, available_depth as i64, balance_factor);
-      result.update(formula, score);
+         no_expand(&mut tree, formula, stack, &mut context, available_depth as i64, balance_factor);
+      update_result(&mut result, observer, iteration, formula, score);
       match action
       {
          ReturnType::NewTree(updated_tree) => tree = updated_tree,
-         ReturnType::DeleteChild => break,
+         ReturnType::DeleteChild =>
+         {
+            report_exhaustion(&mut result, observer, iteration, iteration + 1);
+            break;
+         }
          ReturnType::DoNothing => ()
       }
    }
 
-   memory_summary(&tree);
-   memory_tracker.print_memory_usage();
+   log_memory_summary(&tree);
+   memory_tracker.log_memory_usage();
    result
 }
 
@@ -163,6 +426,7 @@ pub fn memory_limited_search_optional<State, Distr, Res>(available_depth: usize,
 
 /// performs the search for a given number of iterations
 /// NOTE: change searching strategy once the RAM drops below the given level
+///       despite its name, this is a monte carlo tree search with pruning (see `nested_monte_carlo_search`)
 /// TODO this fucntion is a work in progress
 pub fn nested_search<State, Distr, Res>(available_depth: usize,
                                         nb_iterations: usize,
@@ -171,60 +435,560 @@ pub fn nested_search<State, Distr, Res>(available_depth: usize,
    where State: Grammar,
          Distr: Distribution<ScoreType = State::ScoreType>,
          Res: Result<State, ScoreType = State::ScoreType>
+{
+   nested_search_pruned::<State, Distr, Res, CollapseLeastRecentlyVisited>(available_depth,
+                                                                           nb_iterations,
+                                                                           free_memory_size)
+}
+
+/// performs the search for a given number of iterations
+/// NOTE: the tree is pruned with the given strategy once the RAM drops below the given level
+pub fn nested_search_pruned<State, Distr, Res, Prune>(available_depth: usize,
+                                                      nb_iterations: usize,
+                                                      free_memory_size: usize)
+                                                      -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Prune: Pruning
+{
+   nested_search_observed::<State,
+                            Distr,
+                            Res,
+                            NoNormalization,
+                            Prune,
+                            NoWidening,
+                            NoObserver>(available_depth,
+                                        nb_iterations,
+                                        free_memory_size,
+                                        MctsParameters::default(),
+                                        &mut NoObserver)
+}
+
+/// performs the search for a given number of iterations
+/// NOTE: the tree is pruned once the RAM drops below the given level
+///       the parameters control the number of children explored in each node (see `MctsParameters`)
+pub fn nested_search_parameterized<State, Distr, Res, Widen>(available_depth: usize,
+                                                             nb_iterations: usize,
+                                                             free_memory_size: usize,
+                                                             parameters: MctsParameters<Widen>)
+                                                             -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Widen: Widening
+{
+   nested_search_observed::<State,
+                            Distr,
+                            Res,
+                            NoNormalization,
+                            CollapseLeastRecentlyVisited,
+                            Widen,
+                            NoObserver>(available_depth,
+                                        nb_iterations,
+                                        free_memory_size,
+                                        parameters,
+                                        &mut NoObserver)
+}
+
+/// performs the search for a given number of iterations
+/// NOTE: the tree is pruned with the given strategy once the RAM drops below the given level
+///       the scores are normalized before being given to the distributions
+///       the parameters control the number of children explored in each node
+///       the observer is notified of the progress of the search and can stop it
+///       the distributions are timestamped such that the least recently visited subtrees can be found
+///       after a prune that freed less than requested, the tree must grow by a quarter before the next prune
+pub fn nested_search_observed<State, Distr, Res, Norm, Prune, Widen, Obs>(available_depth: usize,
+                                                                          nb_iterations: usize,
+                                                                          free_memory_size: usize,
+                                                                          parameters: MctsParameters<Widen>,
+                                                                          observer: &mut Obs)
+                                                                          -> Res
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Norm: Normalizer<State::ScoreType>,
+         Prune: Pruning,
+         Widen: Widening,
+         Obs: Observer<State>
 {
    let memory_tracker = MemoryTracker::new();
    let free_memory_size: i64 = free_memory_size as i64;
 
-   let mut rng = Xoshiro256Plus::seed_from_u64(0); //from_entropy();
-   let mut tree = Tree::<Distr>::new();
+   let rng = Xoshiro256Plus::seed_from_u64(0); //from_entropy();
+   let mut context = Context::new(rng, Norm::new(), parameters.widening);
+   let mut tree = Tree::<Timestamped<Distr>>::new();
+   let mut tree_size = TreeSize::of(&tree);
    let mut result = Res::new();
 
    // searches while there is memory available
-   // uses a simple linear model to avoid measuring memory at each iteration
+   // the memory used by the tree is tracked incrementally which is cheap enough to be done at each iteration
    let free_memory_base = memory_tracker.free_memory() as i64;
-   let mut iteration_previous = 0;
-   let mut free_memory_current = free_memory_base - (memory_used(&tree) as i64);
-   let mut free_memory_previous = free_memory_current;
-   let mut memory_growth = 0.; // by how much does the memory grow per iteration
-   let step_size = 10000; // refresh memory measure every step_size iterations
+   if free_memory_base < free_memory_size
+   {
+      info!(target: "gambit::search",
+            free_memory_mo = free_memory_base,
+            limit_mo = free_memory_size;
+            "memory already below the limit before the search");
+   }
+   // the tree is not pruned while it uses fewer bytes than this floor
+   let mut pruning_floor = 0;
    for iteration in 0..nb_iterations
    {
+      if !observer.iteration_start(iteration)
+      {
+         break;
+      }
+      context.start_iteration(iteration);
       let formula = Formula::empty();
       let stack = vec![State::root_state()];
-      let (action, formula, score) = expand(&mut tree, formula, stack, &mut rng, available_depth as i64);
-      result.update(formula, score);
+      let (action, formula, score) =
+         expand(&mut tree, formula, stack, &mut context, &mut tree_size, available_depth as i64);
+      update_result(&mut result, observer, iteration, formula, score);
       match action
       {
-         ReturnType::NewTree(updated_tree) => tree = updated_tree,
-         ReturnType::DeleteChild => break,
+         ReturnType::NewTree(updated_tree) =>
+         {
+            tree_size.replace(&tree, &updated_tree);
+            tree = updated_tree
+         }
+         ReturnType::DeleteChild =>
+         {
+            report_exhaustion(&mut result, observer, iteration, iteration + 1);
+            break;
+         }
          ReturnType::DoNothing => ()
       }
-      // updates free_memory_current
-      free_memory_current =
-         free_memory_previous + (((iteration - iteration_previous) as f64) * memory_growth) as i64;
-      if ((iteration - iteration_previous) % step_size == 0) || (free_memory_current < free_memory_size)
+      // prunes the tree if it uses too much memory
+      let free_memory_current = free_memory_base - (tree_size.nb_bytes / 1_000_000) as i64;
+      if (free_memory_current < free_memory_size) && (tree_size.nb_bytes >= pruning_floor)
       {
-         // has a direct computation of the memory used is an expensive operation, we need a large step size to amortize the cost
-         free_memory_current = free_memory_base - (memory_used(&tree) as i64);
-         if free_memory_current < free_memory_size
-         {
-            println!("iteration {}, pruning tree", iteration);
-            tree.prune();
-            // we cannot use RAM usage here as it is not refreshed quicly enough
-            free_memory_current = free_memory_base - (memory_used(&tree) as i64);
-         }
-         memory_growth =
-            (free_memory_current - free_memory_previous) as f64 / (iteration - iteration_previous) as f64;
-         iteration_previous = iteration;
-         free_memory_previous = free_memory_current;
+         // frees the missing memory plus a quarter of the tree in order not to prune at every iteration
+         let missing_bytes = ((free_memory_size - free_memory_current) * 1_000_000) as usize;
+         let nb_bytes = missing_bytes + tree_size.nb_bytes / 4;
+         let nb_bytes_freed = Prune::prune(&mut tree, nb_bytes, &mut context.rng);
+         info!(target: "gambit::search",
+               iteration = iteration,
+               target_bytes = nb_bytes,
+               freed_bytes = nb_bytes_freed;
+               "pruning tree");
+         tree_size = TreeSize::of(&tree);
+         observer.tree_pruned(iteration);
+         // what is left cannot be pruned (or the memory is used elsewhere), backs off until the tree grows
+         pruning_floor =
+            if nb_bytes_freed < nb_bytes { tree_size.nb_bytes + tree_size.nb_bytes / 4 } else { 0 };
       }
    }
 
-   memory_summary(&tree);
-   memory_tracker.print_memory_usage();
+   log_memory_summary(&tree);
+   memory_tracker.log_memory_usage();
+   result
+}
+
+//-----------------------------------------------------------------------------
+// NESTED MONTE CARLO SEARCH
+
+/// performs nested monte carlo searches of the given level until nb_iterations formulas have been evaluated
+/// at each decision, every rule is evaluated with a search of the level below and the best one is played
+/// a search of level 0 being a random expansion of the formula
+/// NOTE: this strategy does not store a tree, its memory use is proportional to the depth of the formulas
+///       the cost of a search grows exponentially with its level, 1 or 2 are reasonable values
+pub fn nested_monte_carlo_search<State, Res>(available_depth: usize,
+                                             level: usize,
+                                             nb_iterations: usize)
+                                             -> Res
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   nested_monte_carlo_search_rollout(available_depth, level, nb_iterations, &mut RandomRollout)
+}
+
+/// performs nested monte carlo searches of the given level until nb_iterations formulas have been evaluated
+/// a search of level 0 being an expansion of the formula following the rollout
+/// NOTE: `UniformRollout` gives searches of level 0 that are not biased toward short formulas
+pub fn nested_monte_carlo_search_rollout<State, Res, Roll>(available_depth: usize,
+                                                           level: usize,
+                                                           nb_iterations: usize,
+                                                           rollout: &mut Roll)
+                                                           -> Res
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Roll: Rollout<State>
+{
+   let mut rng = Xoshiro256Plus::from_entropy();
+   let mut result = Res::new();
+   let mut nb_evaluations_left = nb_iterations;
+   let mut nb_searches = 0;
+   while nb_evaluations_left > 0
+   {
+      let position = Position::root(available_depth);
+      nested_rollout(level, position, rollout, &mut rng, &mut result, &mut nb_evaluations_left);
+      nb_searches += 1;
+   }
+
+   info!(target: "gambit::search",
+         level = level,
+         nb_searches = nb_searches,
+         nb_evaluations = nb_iterations;
+         "nested monte carlo search");
    result
 }
 
+/// performs nested monte carlo searches of the given level until nb_iterations formulas have been evaluated
+/// NOTE: this version is suitable for a grammar that returns an Option<T> score
+///       formulas with no score are considered worse than any formula with a score
+pub fn nested_monte_carlo_search_optional<State, Res>(available_depth: usize,
+                                                      level: usize,
+                                                      nb_iterations: usize)
+                                                      -> Res
+   where State: Grammar<ScoreType = Option<Res::ScoreType>>,
+         Res: Result<State>,
+         Res::ScoreType: Copy + std::fmt::Debug + PartialOrd
+{
+   let result = nested_monte_carlo_search::<State, crate::result::Optional<Res>>(available_depth,
+                                                                                 level,
+                                                                                 nb_iterations);
+   result.get_result()
+}
+
+//-----------------------------------------------------------------------------
+// NESTED ROLLOUT POLICY ADAPTATION
+
+/// performs nested rollout policy adaptation (NRPA) searches of the given level
+/// until nb_iterations formulas have been evaluated
+/// rules are picked with a softmax policy over the decisions that is adapted towards the best formula
+/// a search of level n performs 100 searches of level n-1, a search of level 0 being a single formula
+/// NOTE: this strategy stores one policy per level, their size grows with the number of decisions visited
+///       a search of level n evaluates 100^n formulas, 2 or 3 are reasonable values
+pub fn nested_rollout_policy_adaptation<State, Res>(available_depth: usize,
+                                                    level: usize,
+                                                    nb_iterations: usize)
+                                                    -> Res
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   nested_rollout_policy_adaptation_parameterized(available_depth,
+                                                  level,
+                                                  nb_iterations,
+                                                  NrpaParameters::default())
+}
+
+/// performs nested rollout policy adaptation (NRPA) searches of the given level
+/// until nb_iterations formulas have been evaluated
+/// NOTE: a search of level n evaluates nb_iterations_per_level^n formulas
+pub fn nested_rollout_policy_adaptation_parameterized<State, Res>(available_depth: usize,
+                                                                  level: usize,
+                                                                  nb_iterations: usize,
+                                                                  parameters: NrpaParameters)
+                                                                  -> Res
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   let mut rng = Xoshiro256Plus::from_entropy();
+   let mut result = Res::new();
+   let mut nb_evaluations_left = nb_iterations;
+   let mut nb_searches = 0;
+   while nb_evaluations_left > 0
+   {
+      let mut policies = vec![Policy::new(); level + 1];
+      nrpa(level,
+           &mut policies,
+           &parameters,
+           &mut rng,
+           available_depth as i64,
+           &mut result,
+           &mut nb_evaluations_left);
+      nb_searches += 1;
+   }
+
+   info!(target: "gambit::search",
+         level = level,
+         nb_searches = nb_searches,
+         nb_evaluations = nb_iterations;
+         "nested rollout policy adaptation");
+   result
+}
+
+/// performs nested rollout policy adaptation (NRPA) searches of the given level
+/// until nb_iterations formulas have been evaluated
+/// NOTE: this version is suitable for a grammar that returns an Option<T> score
+///       formulas with no score are considered worse than any formula with a score
+pub fn nested_rollout_policy_adaptation_optional<State, Res>(available_depth: usize,
+                                                             level: usize,
+                                                             nb_iterations: usize)
+                                                             -> Res
+   where State: Grammar<ScoreType = Option<Res::ScoreType>>,
+         Res: Result<State>,
+         Res::ScoreType: Copy + std::fmt::Debug + PartialOrd
+{
+   let result =
+      nested_rollout_policy_adaptation::<State, crate::result::Optional<Res>>(available_depth,
+                                                                              level,
+                                                                              nb_iterations);
+   result.get_result()
+}
+
+//-----------------------------------------------------------------------------
+// BEAM SEARCH
+
+/// performs beam searches until nb_iterations formulas have been evaluated
+/// at each decision, the beam_width best partial formulas, each scored with a random expansion, are kept
+/// as those expansions are random, each search can find different formulas
+/// NOTE: this strategy does not store a tree, its memory use is proportional to beam_width
+pub fn beam_search<State, Res>(available_depth: usize, beam_width: usize, nb_iterations: usize) -> Res
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   beam_search_rollout(available_depth, beam_width, nb_iterations, &mut RandomRollout)
+}
+
+/// performs beam searches until nb_iterations formulas have been evaluated
+/// at each decision, the beam_width best partial formulas are kept
+/// each partial formula being scored with a completion following the rollout
+/// NOTE: `UniformRollout` scores the partial formulas with completions not biased toward short formulas
+pub fn beam_search_rollout<State, Res, Roll>(available_depth: usize,
+                                             beam_width: usize,
+                                             nb_iterations: usize,
+                                             rollout: &mut Roll)
+                                             -> Res
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Roll: Rollout<State>
+{
+   let mut rng = Xoshiro256Plus::from_entropy();
+   let mut result = Res::new();
+   let mut nb_evaluations_left = nb_iterations;
+   let mut nb_searches = 0;
+   while nb_evaluations_left > 0
+   {
+      beam(available_depth,
+           beam_width,
+           &mut result,
+           &mut nb_evaluations_left,
+           |position, result, nb_evaluations_left| {
+              rollout_estimate(position, rollout, &mut rng, result, nb_evaluations_left)
+           });
+      nb_searches += 1;
+   }
+
+   info!(target: "gambit::search",
+         beam_width = beam_width,
+         nb_searches = nb_searches,
+         nb_evaluations = nb_iterations;
+         "beam search");
+   result
+}
+
+/// performs a single beam search, stopping early if nb_iterations formulas have been evaluated
+/// at each decision, the beam_width best partial formulas, according to the heuristic, are kept
+/// NOTE: only complete formulas count as evaluations, the heuristic is expected to be cheap
+pub fn beam_search_heuristic<State, Res, Heur>(available_depth: usize,
+                                               beam_width: usize,
+                                               nb_iterations: usize,
+                                               heuristic: &mut Heur)
+                                               -> Res
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Heur: Heuristic<State>
+{
+   let mut result = Res::new();
+   let mut nb_evaluations_left = nb_iterations;
+   beam(available_depth, beam_width, &mut result, &mut nb_evaluations_left, |position, _, _| {
+      Some(heuristic.estimate(&position.formula, &position.stack))
+   });
+
+   info!(target: "gambit::search",
+         beam_width = beam_width,
+         nb_evaluations = nb_iterations - nb_evaluations_left;
+         "beam search");
+   result
+}
+
+//-----------------------------------------------------------------------------
+// GENETIC PROGRAMMING
+
+/// performs a grammar guided genetic programming search until nb_iterations formulas have been evaluated
+/// the individuals are derivation trees, evolved with subtree crossovers and mutations
+/// NOTE: this is a baseline to compare the tree search with, given an identical number of iterations
+pub fn genetic_search<State, Res>(available_depth: usize, population_size: usize, nb_iterations: usize) -> Res
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   genetic_search_parameterized(available_depth, population_size, nb_iterations, GeneticParameters::default())
+}
+
+/// performs a grammar guided genetic programming search until nb_iterations formulas have been evaluated
+/// NOTE: the parameters set the selection pressure, the share of crossovers and the limit on bloat
+pub fn genetic_search_parameterized<State, Res>(available_depth: usize,
+                                                population_size: usize,
+                                                nb_iterations: usize,
+                                                parameters: GeneticParameters)
+                                                -> Res
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   let mut rng = Xoshiro256Plus::from_entropy();
+   let mut result = Res::new();
+   let mut nb_evaluations_left = nb_iterations;
+   let nb_generations =
+      evolve(available_depth, population_size, &parameters, &mut rng, &mut result, &mut nb_evaluations_left);
+
+   info!(target: "gambit::search",
+         population_size = population_size,
+         nb_generations = nb_generations,
+         nb_evaluations = nb_iterations - nb_evaluations_left;
+         "genetic search");
+   result
+}
+
+/// performs a grammar guided genetic programming search until nb_iterations formulas have been evaluated
+/// NOTE: this version is suitable for a grammar that returns an Option<T> score
+///       formulas with no score are considered worse than any formula with a score
+pub fn genetic_search_optional<State, Res>(available_depth: usize,
+                                           population_size: usize,
+                                           nb_iterations: usize)
+                                           -> Res
+   where State: Grammar<ScoreType = Option<Res::ScoreType>>,
+         Res: Result<State>,
+         Res::ScoreType: Copy + std::fmt::Debug + PartialOrd
+{
+   let result =
+      genetic_search::<State, crate::result::Optional<Res>>(available_depth, population_size, nb_iterations);
+   result.get_result()
+}
+
+//-----------------------------------------------------------------------------
+// PORTFOLIO
+
+/// performs the search for a given number of iterations, shared between several distributions
+/// the iterations are given in priority to the distributions that improve the most often
+/// and the searches that stagnate are restarted (see `Portfolio` to choose the distributions)
+/// WARNING: this function is memory hungry and could fill the RAM
+pub fn portfolio_search<State, Res>(available_depth: usize, nb_iterations: usize) -> Res
+   where State: Grammar<ScoreType = f64> + 'static,
+         Res: Result<State, ScoreType = f64> + 'static
+{
+   Portfolio::<State, Res>::new(available_depth).with_arm::<ThompsonMax>(0)
+                                                .with_arm::<UcbTuned>(1)
+                                                .with_arm::<RandomSearch>(2)
+                                                .search(nb_iterations)
+}
+
+//-----------------------------------------------------------------------------
+// EXHAUSTIVE SEARCH
+
+/// evaluates all the formulas that can be derived within the available depth, up to nb_iterations formulas
+/// NOTE: if the search space is exhausted, the result contains its global optimum
+///       this is only practical for small grammars and depths (`count_formulas` gives the size of the space)
+pub fn exhaustive_search<State, Res>(available_depth: usize, nb_iterations: usize) -> Res
+   where State: Grammar,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   exhaustive_search_observed::<State, Res, NoObserver>(available_depth, nb_iterations, &mut NoObserver)
+}
+
+/// evaluates all the formulas that can be derived within the available depth, up to nb_iterations formulas
+/// NOTE: the observer is notified of the progress of the search and can stop it
+///       it is told if the search space has been exhausted
+pub fn exhaustive_search_observed<State, Res, Obs>(available_depth: usize,
+                                                   nb_iterations: usize,
+                                                   observer: &mut Obs)
+                                                   -> Res
+   where State: Grammar,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Obs: Observer<State>
+{
+   let mut result = Res::new();
+   let mut iteration = 0;
+   let exhausted = enumerate(available_depth, |formula| {
+                      if (iteration >= nb_iterations) || !observer.iteration_start(iteration)
+                      {
+                         return false;
+                      }
+                      let score = formula.evaluate();
+                      update_result(&mut result, observer, iteration, formula, score);
+                      iteration += 1;
+                      true
+                   });
+
+   if exhausted
+   {
+      // the formulas are enumerated without repetitions
+      report_exhaustion(&mut result, observer, iteration.saturating_sub(1), iteration);
+   }
+   else
+   {
+      info!(target: "gambit::search", nb_formulas = iteration; "exhaustive search stopped");
+   }
+   result
+}
+
+/// evaluates all the formulas that can be derived within the available depth, up to nb_iterations formulas
+/// NOTE: this version is suitable for a grammar that returns an Option<T> score
+pub fn exhaustive_search_optional<State, Res>(available_depth: usize, nb_iterations: usize) -> Res
+   where State: Grammar<ScoreType = Option<Res::ScoreType>>,
+         Res: Result<State>,
+         Res::ScoreType: Copy + std::fmt::Debug
+{
+   let result = exhaustive_search::<State, crate::result::Optional<Res>>(available_depth, nb_iterations);
+   result.get_result()
+}
+
+//-----------------------------------------------------------------------------
+// UNIFORM RANDOM SEARCH
+
+/// evaluates nb_iterations formulas with at most max_size states
+/// the size of each formula is picked uniformly (among the sizes that can be produced by the grammar)
+/// and the formula is picked uniformly among the formulas of that size
+/// NOTE: this is a random search baseline that, unlike `RandomSearch`, is not biased toward short formulas
+///       the formulas are limited by their number of states rather than by a depth
+pub fn uniform_random_search<State, Res>(max_size: usize, nb_iterations: usize) -> Res
+   where State: Grammar,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   let mut rng = Xoshiro256Plus::from_entropy();
+   let mut sampler = SizeSampler::<State>::new(max_size);
+   let sizes: Vec<usize> = (1..=max_size).filter(|&size| sampler.nb_formulas(size) > 0.).collect();
+   let mut result = Res::new();
+   if !sizes.is_empty()
+   {
+      for _ in 0..nb_iterations
+      {
+         let size = sizes[rng.gen_range(0, sizes.len())];
+         let formula = sampler.sample(size, &mut rng).expect("uniform_random_search: invalid size.");
+         let score = formula.evaluate();
+         result.update(formula, score);
+      }
+   }
+
+   info!(target: "gambit::search",
+         max_size = max_size,
+         nb_sizes = sizes.len(),
+         nb_formulas = sizes.iter().map(|&size| sampler.nb_formulas(size)).sum::<f64>();
+         "uniform random search");
+   result
+}
+
+/// evaluates nb_iterations formulas with at most max_size states, with uniformly distributed sizes
+/// NOTE: this version is suitable for a grammar that returns an Option<T> score
+pub fn uniform_random_search_optional<State, Res>(max_size: usize, nb_iterations: usize) -> Res
+   where State: Grammar<ScoreType = Option<Res::ScoreType>>,
+         Res: Result<State>,
+         Res::ScoreType: Copy + std::fmt::Debug
+{
+   let result = uniform_random_search::<State, crate::result::Optional<Res>>(max_size, nb_iterations);
+   result.get_result()
+}
+
 // TODO implement slower memory explore
 
 // TODO the evolutionnary strategy crate has a nice idea :
@@ -0,0 +1,189 @@
+use rand::Rng;
+use crate::grammar::{Grammar, Formula};
+use crate::result::Result;
+use super::rollout::{Rollout, rollout_expand};
+use super::position::Position;
+
+//-----------------------------------------------------------------------------
+// TYPES
+
+/// a complete formula and the decisions that lead to it
+pub struct Playout<State: Grammar>
+{
+   choices: Vec<usize>, // index of the rule picked at each decision, starting from the position explored
+   formula: Formula<State>,
+   score: State::ScoreType
+}
+
+//-----------------------------------------------------------------------------
+// FUNCTIONS
+
+/// completes the position with a playout following the rollout
+fn rollout_playout<State, RNG, Roll>(position: Position<State>,
+                                     rollout: &mut Roll,
+                                     rng: &mut RNG)
+                                     -> Playout<State>
+   where State: Grammar,
+         RNG: Rng,
+         Roll: Rollout<State>
+{
+   let mut choices = Vec::new();
+   let Position { formula, stack, available_depth } = position;
+   let (formula, score) =
+      rollout_expand(formula, stack, rng, available_depth, rollout, |rule_index| choices.push(rule_index));
+   Playout { choices, formula, score }
+}
+
+/// performs a nested monte carlo search of the given level from the position
+/// at each decision, every rule is evaluated with a search of the level below
+/// and the best sequence of decisions found so far is followed
+/// returns None if there was no evaluation left to find a formula
+/// NOTE: a search of level 0 is a playout following the rollout
+pub fn nested_rollout<State, RNG, Res, Roll>(level: usize,
+                                             mut position: Position<State>,
+                                             rollout: &mut Roll,
+                                             rng: &mut RNG,
+                                             result: &mut Res,
+                                             nb_evaluations_left: &mut usize)
+                                             -> Option<Playout<State>>
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         RNG: Rng,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Roll: Rollout<State>
+{
+   if *nb_evaluations_left == 0
+   {
+      return None;
+   }
+   if level == 0
+   {
+      *nb_evaluations_left -= 1;
+      let playout = rollout_playout(position, rollout, rng);
+      result.update(playout.formula.clone(), playout.score);
+      return Some(playout);
+   }
+
+   let mut best_playout: Option<Playout<State>> = None;
+   let mut nb_choices_played = 0;
+   while let Some(rules) = position.next_decision()
+   {
+      for (rule_index, rule) in rules.iter().enumerate()
+      {
+         let mut child_position = position.clone();
+         child_position.play(rule);
+         let child_playout =
+            nested_rollout(level - 1, child_position, rollout, rng, result, nb_evaluations_left);
+         let child_playout = match child_playout
+         {
+            None => return best_playout, // no evaluation left
+            Some(playout) => playout
+         };
+         let is_better = best_playout.as_ref().is_none_or(|best| child_playout.score > best.score);
+         if is_better
+         {
+            // the best playout always starts with the choices played so far
+            let mut choices = best_playout.map_or(Vec::new(), |best| best.choices);
+            choices.truncate(nb_choices_played);
+            choices.push(rule_index);
+            choices.extend(child_playout.choices);
+            best_playout = Some(Playout { choices, ..child_playout });
+         }
+      }
+      // follows the best sequence of decisions found so far
+      let rule_index = best_playout.as_ref().unwrap().choices[nb_choices_played];
+      position.play(&rules[rule_index]);
+      nb_choices_played += 1;
+   }
+
+   // the formula required no decision at all
+   if best_playout.is_none()
+   {
+      *nb_evaluations_left -= 1;
+      let score = position.formula.evaluate();
+      result.update(position.formula.clone(), score);
+      best_playout = Some(Playout { choices: Vec::new(), formula: position.formula, score });
+   }
+   best_playout
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+   use crate::result::Single;
+   use super::super::rollout::RandomRollout;
+   use super::super::test_grammar::Bits;
+
+   /// returns the mean score of the playouts of a nested search of the given level, over several seeds
+   fn mean_score(level: usize) -> f64
+   {
+      let nb_searches = 20;
+      let mut total = 0.;
+      for seed in 0..nb_searches
+      {
+         let mut rng = Xoshiro256Plus::seed_from_u64(seed);
+         let mut result = Single::<Bits>::new();
+         let mut nb_evaluations_left = usize::MAX;
+         let position = Position::root(100);
+         let mut rollout = RandomRollout;
+         let playout =
+            nested_rollout(level, position, &mut rollout, &mut rng, &mut result, &mut nb_evaluations_left);
+         total += playout.unwrap().score;
+      }
+      total / nb_searches as f64
+   }
+
+   #[test]
+   fn returns_valid_formulas()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      for level in 0..3
+      {
+         for &available_depth in &[0, 3, 100]
+         {
+            let mut result = Single::<Bits>::new();
+            let mut nb_evaluations_left = 1000;
+            let position = Position::root(available_depth);
+            let playout = nested_rollout(level,
+                                         position,
+                                         &mut RandomRollout,
+                                         &mut rng,
+                                         &mut result,
+                                         &mut nb_evaluations_left).unwrap();
+            assert_eq!(playout.formula.len(), 8);
+            assert_eq!(playout.score, playout.formula.evaluate());
+            // a decision is made per bit while there is depth available
+            assert_eq!(playout.choices.len(), available_depth.min(8));
+            assert!(result.score >= playout.score);
+         }
+      }
+   }
+
+   #[test]
+   fn higher_levels_find_better_formulas()
+   {
+      let scores: Vec<f64> = (0..3).map(mean_score).collect();
+      assert!((scores[0] < scores[1]) && (scores[1] < scores[2]));
+   }
+
+   #[test]
+   fn stops_when_there_is_no_evaluation_left()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut result = Single::<Bits>::new();
+      let mut nb_evaluations_left = 5;
+      let position = Position::root(100);
+      let playout =
+         nested_rollout(2, position, &mut RandomRollout, &mut rng, &mut result, &mut nb_evaluations_left);
+      assert_eq!(nb_evaluations_left, 0);
+      // the best playout found before the budget ran out
+      assert!(playout.is_some());
+      let position = Position::root(100);
+      let playout =
+         nested_rollout(0, position, &mut RandomRollout, &mut rng, &mut result, &mut nb_evaluations_left);
+      assert!(playout.is_none());
+   }
+}
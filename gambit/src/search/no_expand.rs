@@ -4,6 +4,8 @@ use crate::distribution::Distribution;
 use crate::grammar::{Grammar, Formula};
 use super::random_expand::random_expand;
 use super::tree::*;
+use super::normalizer::Normalizer;
+use super::context::Context;
 
 //-----------------------------------------------------------------------------
 // FUNCTION
@@ -18,10 +20,15 @@ impl<Distr: Distribution> Tree<Distr>
       {
          match tree
          {
-            Tree::Node(box Node { children, .. }) => children.iter().fold((0, 0), |(na, ta), child| {
-                                                                       let (n, t) = length(child);
-                                                                       (na + n, ta + t + n)
-                                                                    }),
+            Tree::Node(box Node { children, .. }) =>
+            {
+               // untried children are leafs of length 0
+               let nb_untried = children.nb_untried();
+               children.visited().fold((nb_untried, 0), |(na, ta), (_, child)| {
+                                    let (n, t) = length(child);
+                                    (na + n, ta + t + n)
+                                 })
+            }
             Tree::Deleted => (0, 0),
             Tree::Leaf | Tree::KnownLeaf(_) => (1, 0)
          }
@@ -46,7 +53,7 @@ impl<Distr: Distribution> Tree<Distr>
 /// given a balance factor and a number of visits
 /// NOTE: we are modeling the growth of the tree (and all its subtrees) with the formula:
 /// balance_factor * lne(nb_visit) = mean_formula_length
-fn expected_formula_length(balance_factor: f64, nb_visit: u64) -> i64
+pub fn expected_formula_length(balance_factor: f64, nb_visit: u64) -> i64
 {
    (lne(nb_visit as f64) * balance_factor) as i64
 }
@@ -54,19 +61,20 @@ fn expected_formula_length(balance_factor: f64, nb_visit: u64) -> i64
 //-----------------------------------------------------------------------------
 // EXPAND
 
-/// takes a tree, its prior, a random number generator and the available depth and expand the tree
+/// takes a tree, its prior, the context of the search and the available depth and expand the tree
 /// return the result of the expansion as a (ReturnType, formula, Option<score>)
 /// NOTE: this function will not grow the tree, instead it will only update priors
-pub fn no_expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
-                                    mut formula: Formula<State>,
-                                    mut stack: Vec<State>,
-                                    rng: &mut RNG,
-                                    available_depth: i64,
-                                    balance_factor: f64)
-                                    -> (ReturnType<Tree<Distr>>, Formula<State>, State::ScoreType)
+pub fn no_expand<State, Distr, RNG, Norm, Widen>(mut tree: &mut Tree<Distr>,
+                                                 mut formula: Formula<State>,
+                                                 mut stack: Vec<State>,
+                                                 context: &mut Context<State, RNG, Norm, Widen>,
+                                                 available_depth: i64,
+                                                 balance_factor: f64)
+                                                 -> (ReturnType<Tree<Distr>>, Formula<State>, State::ScoreType)
    where State: Grammar,
          Distr: Distribution<ScoreType = State::ScoreType>,
-         RNG: Rng
+         RNG: Rng,
+         Norm: Normalizer<State::ScoreType>
 {
    match stack.last()
    {
@@ -74,6 +82,7 @@ pub fn no_expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
       {
          // terminal node, we evaluate the formula and backpropagate
          let score = formula.evaluate();
+         context.normalizer.observe(score);
          (ReturnType::DeleteChild, formula, score)
       }
       Some(&state) =>
@@ -86,14 +95,14 @@ pub fn no_expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
                // terminal state
                stack.pop();
                formula.push(state);
-               no_expand(&mut tree, formula, stack, rng, available_depth, balance_factor)
+               no_expand(&mut tree, formula, stack, context, available_depth, balance_factor)
             }
             [rule] =>
             {
                // single rule, we can focus on it
                stack.pop();
                stack.extend(rule);
-               no_expand(&mut tree, formula, stack, rng, available_depth, balance_factor)
+               no_expand(&mut tree, formula, stack, context, available_depth, balance_factor)
             }
             rules =>
             {
@@ -107,8 +116,9 @@ pub fn no_expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
                      let mut distribution = Distr::new();
                      let length = expected_formula_length(balance_factor, distribution.nb_visit());
                      let search_depth = length + available_depth - 1;
-                     let (formula, score) = random_expand(formula, stack, rng, search_depth);
-                     distribution.update(score);
+                     let (formula, score) = random_expand(formula, stack, &mut context.rng, search_depth);
+                     context.normalizer.observe(score);
+                     distribution.update(context.normalizer.normalize(score));
                      let known_leaf = Tree::KnownLeaf(Box::new(distribution));
                      (ReturnType::NewTree(known_leaf), formula, score)
                   }
@@ -117,34 +127,46 @@ pub fn no_expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
                      // non terminal state, we explore randomly (at a depth function of the balance_factor)
                      let length = expected_formula_length(balance_factor, distribution.nb_visit());
                      let search_depth = length + available_depth - 1;
-                     let (formula, score) = random_expand(formula, stack, rng, search_depth);
-                     distribution.update(score);
+                     let (formula, score) = random_expand(formula, stack, &mut context.rng, search_depth);
+                     context.normalizer.observe(score);
+                     distribution.update(context.normalizer.normalize(score));
                      (ReturnType::DoNothing, formula, score)
                   }
-                  Tree::Node(box Node { ref mut distribution, ref mut children }) =>
+                  Tree::Node(box Node { ref mut distribution, ref mut children, .. }) =>
                   {
                      // we choose a child using the prior and explore it
+                     let rng = &mut context.rng;
                      let index_best_child = Tree::best_child(children, distribution, rng, available_depth);
+                     let first_choice = context.choose(state, index_best_child);
                      // update the stack
                      let rule = rules[index_best_child].clone();
                      stack.pop();
                      stack.extend(rule);
-                     // expand the child
-                     let (action, formula, score) = no_expand(&mut children[index_best_child],
-                                                              formula,
-                                                              stack,
-                                                              rng,
-                                                              available_depth,
-                                                              balance_factor);
-                     distribution.update(score);
+                     // expand the child (an untried child being a leaf that is not stored yet)
+                     let mut untried_child = Tree::Leaf;
+                     let child = if children.is_untried(index_best_child)
+                     {
+                        &mut untried_child
+                     }
+                     else
+                     {
+                        children.get_mut(index_best_child).expect("Expand: tried to explore a deleted tree!")
+                     };
+                     let depth = available_depth - 1;
+                     let (action, formula, score) =
+                        no_expand(child, formula, stack, context, depth, balance_factor);
+                     let normalized_score = context.normalizer.normalize(score);
+                     distribution.update(normalized_score);
+                     context.update_rules(distribution, state, first_choice, normalized_score);
                      match action
                      {
                         ReturnType::DeleteChild =>
                         {
-                           children[index_best_child] = Tree::Deleted;
-                           if children.iter().all(|t| t.is_deleted())
+                           children.replace(index_best_child, Tree::Deleted);
+                           if children.is_exhausted() || (available_depth <= 0)
                            {
                               // no more children, we can delete this node
+                              // (once the depth is exhausted, only the first child can be explored)
                               (ReturnType::DeleteChild, formula, score)
                            }
                            else
@@ -161,7 +183,7 @@ pub fn no_expand<State, Distr, RNG>(mut tree: &mut Tree<Distr>,
                         ReturnType::NewTree(child_tree) =>
                         {
                            // we can replace this child and update its prior
-                           children[index_best_child] = child_tree;
+                           children.replace(index_best_child, child_tree);
                            (ReturnType::DoNothing, formula, score)
                         }
                      }
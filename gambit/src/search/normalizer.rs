@@ -0,0 +1,265 @@
+use std::collections::VecDeque;
+use std::cmp::Ordering;
+
+/// number of recent scores kept to compute ranks
+const RANK_WINDOW: usize = 1024;
+
+/// maps the scores into a common scale before they reach the distributions
+/// so that the exploration constants mean the same thing across problems
+pub trait Normalizer<ScoreType>
+{
+   /// returns a normalizer that has not seen any score
+   fn new() -> Self;
+
+   /// registers a newly evaluated score
+   fn observe(&mut self, score: ScoreType);
+
+   /// maps a score into [0,1] given the scores observed so far
+   fn normalize(&self, score: ScoreType) -> ScoreType;
+}
+
+//-----------------------------------------------------------------------------
+// IDENTITY
+
+/// leaves the scores untouched
+pub struct NoNormalization {}
+
+impl<ScoreType> Normalizer<ScoreType> for NoNormalization
+{
+   fn new() -> Self
+   {
+      NoNormalization {}
+   }
+
+   fn observe(&mut self, _score: ScoreType) {}
+
+   fn normalize(&self, score: ScoreType) -> ScoreType
+   {
+      score
+   }
+}
+
+//-----------------------------------------------------------------------------
+// MIN MAX
+
+/// rescales the scores linearly using the minimum and maximum scores seen so far
+pub struct MinMaxNormalization
+{
+   min: f64,
+   max: f64
+}
+
+impl Normalizer<f64> for MinMaxNormalization
+{
+   fn new() -> Self
+   {
+      MinMaxNormalization { min: std::f64::INFINITY, max: std::f64::NEG_INFINITY }
+   }
+
+   /// NOTE: non finite scores are ignored as they would make all the other scores collapse to 0 or 1
+   fn observe(&mut self, score: f64)
+   {
+      if score.is_finite()
+      {
+         self.min = self.min.min(score);
+         self.max = self.max.max(score);
+      }
+   }
+
+   /// NOTE: returns 0.5 until two different scores have been observed
+   ///       non finite scores are clamped to [0,1], NaN being mapped to 0
+   fn normalize(&self, score: f64) -> f64
+   {
+      if self.max > self.min
+      {
+         let normalized = (score - self.min) / (self.max - self.min);
+         if normalized.is_nan() { 0. } else { normalized.clamp(0., 1.) }
+      }
+      else
+      {
+         0.5
+      }
+   }
+}
+
+/// normalizes the scores, if any
+impl Normalizer<Option<f64>> for MinMaxNormalization
+{
+   fn new() -> Self
+   {
+      <MinMaxNormalization as Normalizer<f64>>::new()
+   }
+
+   fn observe(&mut self, score: Option<f64>)
+   {
+      if let Some(score) = score
+      {
+         self.observe(score)
+      }
+   }
+
+   fn normalize(&self, score: Option<f64>) -> Option<f64>
+   {
+      score.map(|score| self.normalize(score))
+   }
+}
+
+//-----------------------------------------------------------------------------
+// RANK
+
+/// returns the index of the first element of a sorted slice that does not satisfy the predicate
+fn first_index<F: Fn(f64) -> bool>(sorted: &[f64], predicate: F) -> usize
+{
+   match sorted.binary_search_by(|&s| if predicate(s) { Ordering::Less } else { Ordering::Greater })
+   {
+      Ok(index) | Err(index) => index
+   }
+}
+
+/// replaces the scores with their rank among the latest scores seen
+/// NOTE: robust to outliers and to scores spanning several orders of magnitude
+pub struct RankNormalization
+{
+   recent: VecDeque<f64>, // latest scores, in order of arrival
+   sorted: Vec<f64>       // latest scores, sorted
+}
+
+impl Normalizer<f64> for RankNormalization
+{
+   fn new() -> Self
+   {
+      RankNormalization { recent: VecDeque::with_capacity(RANK_WINDOW),
+                          sorted: Vec::with_capacity(RANK_WINDOW) }
+   }
+
+   fn observe(&mut self, score: f64)
+   {
+      if score.is_nan()
+      {
+         return;
+      }
+      if self.recent.len() == RANK_WINDOW
+      {
+         // forgets the oldest score
+         let oldest = self.recent.pop_front().unwrap();
+         let index = self.sorted.binary_search_by(|s| s.partial_cmp(&oldest).unwrap()).unwrap();
+         self.sorted.remove(index);
+      }
+      let index = match self.sorted.binary_search_by(|s| s.partial_cmp(&score).unwrap())
+      {
+         Ok(index) | Err(index) => index
+      };
+      self.sorted.insert(index, score);
+      self.recent.push_back(score);
+   }
+
+   /// NOTE: ties count for half and the result is 0.5 until a score has been observed
+   fn normalize(&self, score: f64) -> f64
+   {
+      if self.sorted.is_empty()
+      {
+         return 0.5;
+      }
+      let nb_below = first_index(&self.sorted, |s| s < score);
+      let nb_below_or_equal = first_index(&self.sorted, |s| s <= score);
+      (nb_below + nb_below_or_equal) as f64 / (2. * self.sorted.len() as f64)
+   }
+}
+
+/// normalizes the scores, if any
+impl Normalizer<Option<f64>> for RankNormalization
+{
+   fn new() -> Self
+   {
+      <RankNormalization as Normalizer<f64>>::new()
+   }
+
+   fn observe(&mut self, score: Option<f64>)
+   {
+      if let Some(score) = score
+      {
+         self.observe(score)
+      }
+   }
+
+   fn normalize(&self, score: Option<f64>) -> Option<f64>
+   {
+      score.map(|score| self.normalize(score))
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   #[test]
+   fn no_normalization_is_the_identity()
+   {
+      let mut normalizer = <NoNormalization as Normalizer<f64>>::new();
+      normalizer.observe(-1e10);
+      assert_eq!(normalizer.normalize(-1e10), -1e10);
+      assert_eq!(normalizer.normalize(3.), 3.);
+   }
+
+   #[test]
+   fn min_max_rescales_into_the_unit_interval()
+   {
+      let mut normalizer = <MinMaxNormalization as Normalizer<f64>>::new();
+      assert_eq!(normalizer.normalize(-5.), 0.5);
+      normalizer.observe(-5.);
+      assert_eq!(normalizer.normalize(-5.), 0.5);
+      normalizer.observe(15.);
+      assert_eq!(normalizer.normalize(-5.), 0.);
+      assert_eq!(normalizer.normalize(0.), 0.25);
+      assert_eq!(normalizer.normalize(15.), 1.);
+      // optional scores
+      assert_eq!(normalizer.normalize(Some(5.)), Some(0.5));
+      assert_eq!(normalizer.normalize(None), None);
+   }
+
+   #[test]
+   fn min_max_ignores_non_finite_scores()
+   {
+      let mut normalizer = <MinMaxNormalization as Normalizer<f64>>::new();
+      normalizer.observe(0.);
+      normalizer.observe(std::f64::NEG_INFINITY);
+      normalizer.observe(std::f64::NAN);
+      normalizer.observe(10.);
+      normalizer.observe(std::f64::INFINITY);
+      assert_eq!(normalizer.normalize(5.), 0.5);
+      assert_eq!(normalizer.normalize(std::f64::NEG_INFINITY), 0.);
+      assert_eq!(normalizer.normalize(std::f64::INFINITY), 1.);
+      assert_eq!(normalizer.normalize(std::f64::NAN), 0.);
+   }
+
+   #[test]
+   fn rank_is_robust_to_the_scale_of_the_scores()
+   {
+      let mut normalizer = <RankNormalization as Normalizer<f64>>::new();
+      assert_eq!(normalizer.normalize(1.), 0.5);
+      for &score in &[-1e10, -1e5, -1., -1e-5]
+      {
+         normalizer.observe(score);
+      }
+      normalizer.observe(std::f64::NAN);
+      assert_eq!(normalizer.normalize(-1e20), 0.);
+      assert_eq!(normalizer.normalize(-1e10), 0.125);
+      assert_eq!(normalizer.normalize(-1e3), 0.5);
+      assert_eq!(normalizer.normalize(0.), 1.);
+   }
+
+   #[test]
+   fn rank_forgets_old_scores()
+   {
+      let mut normalizer = <RankNormalization as Normalizer<f64>>::new();
+      normalizer.observe(1.);
+      for _ in 0..RANK_WINDOW
+      {
+         normalizer.observe(0.);
+      }
+      // the only score above 0 left the window
+      assert_eq!(normalizer.normalize(0.), 0.5);
+      assert_eq!(normalizer.normalize(1.), 1.);
+   }
+}
@@ -0,0 +1,284 @@
+use rand::Rng;
+use std::collections::HashMap;
+use crate::grammar::{Grammar, Formula};
+use crate::result::Result;
+use super::random_expand::policy_expand;
+
+//-----------------------------------------------------------------------------
+// TYPES
+
+/// the parameters of a nested rollout policy adaptation search
+pub struct NrpaParameters
+{
+   pub nb_iterations_per_level: usize, // number of searches of the level below performed by a search
+   pub learning_rate: f64              // how far the policy moves towards the best playout after a search
+}
+
+impl Default for NrpaParameters
+{
+   fn default() -> Self
+   {
+      NrpaParameters { nb_iterations_per_level: 100, learning_rate: 1. }
+   }
+}
+
+/// identifies a decision: the number of decisions taken before it and the state being expanded
+/// NOTE: as in the original algorithm, where a move is coded with the step at which it is played,
+///       the same state can be expanded differently depending on where it appears in the formula
+type Code<State> = (usize, State);
+
+/// a softmax policy over the rules of each decision
+/// the probability of picking a rule is proportional to exp(weight(code, rule index))
+#[derive(Clone)]
+pub struct Policy<State: Grammar>
+{
+   weights: HashMap<(Code<State>, usize), f64> // missing weights are equal to 0
+}
+
+/// a decision taken during a playout
+#[derive(Clone, Copy)]
+struct Choice<State: Grammar>
+{
+   code: Code<State>,
+   rule_index: usize,
+   nb_rules: usize
+}
+
+/// a complete formula and the decisions that lead to it
+pub struct Playout<State: Grammar>
+{
+   choices: Vec<Choice<State>>,
+   formula: Formula<State>,
+   score: State::ScoreType
+}
+
+//-----------------------------------------------------------------------------
+// POLICY
+
+impl<State: Grammar> Policy<State>
+{
+   /// creates a uniform policy
+   pub fn new() -> Policy<State>
+   {
+      Policy { weights: HashMap::new() }
+   }
+
+   /// returns the weight associated with a rule of a decision
+   fn weight(&self, code: Code<State>, rule_index: usize) -> f64
+   {
+      self.weights.get(&(code, rule_index)).cloned().unwrap_or(0.)
+   }
+
+   /// returns exp(weight) for all the rules of a decision
+   /// NOTE: the weights are shifted by their maximum to avoid overflows
+   fn exp_weights(&self, code: Code<State>, nb_rules: usize) -> Vec<f64>
+   {
+      let weights: Vec<f64> = (0..nb_rules).map(|rule_index| self.weight(code, rule_index)).collect();
+      let max_weight = weights.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max);
+      weights.iter().map(|weight| (weight - max_weight).exp()).collect()
+   }
+
+   /// returns the probability of picking each of the rules of a decision
+   fn probabilities(&self, code: Code<State>, nb_rules: usize) -> Vec<f64>
+   {
+      let exp_weights = self.exp_weights(code, nb_rules);
+      let total: f64 = exp_weights.iter().sum();
+      exp_weights.iter().map(|exp_weight| exp_weight / total).collect()
+   }
+
+   /// picks a rule for the decision with a probability proportional to exp(weight)
+   fn choose<RNG: Rng>(&self, code: Code<State>, nb_rules: usize, rng: &mut RNG) -> usize
+   {
+      let exp_weights = self.exp_weights(code, nb_rules);
+      let total: f64 = exp_weights.iter().sum();
+      let mut target = rng.gen::<f64>() * total;
+      for (rule_index, exp_weight) in exp_weights.iter().enumerate()
+      {
+         target -= exp_weight;
+         if target <= 0.
+         {
+            return rule_index;
+         }
+      }
+      nb_rules - 1
+   }
+
+   /// makes the given choices more likely
+   /// NOTE: the gradients are computed with the policy as it was before the adaptation
+   fn adapt(&mut self, choices: &[Choice<State>], learning_rate: f64)
+   {
+      let probabilities: Vec<Vec<f64>> =
+         choices.iter().map(|choice| self.probabilities(choice.code, choice.nb_rules)).collect();
+      for (choice, probabilities) in choices.iter().zip(probabilities)
+      {
+         for (rule_index, probability) in probabilities.into_iter().enumerate()
+         {
+            *self.weights.entry((choice.code, rule_index)).or_insert(0.) -= learning_rate * probability;
+         }
+         *self.weights.get_mut(&(choice.code, choice.rule_index)).unwrap() += learning_rate;
+      }
+   }
+}
+
+//-----------------------------------------------------------------------------
+// SEARCH
+
+/// expands the root of the grammar into a complete formula using the policy to take decisions
+fn policy_playout<State, RNG>(policy: &Policy<State>, rng: &mut RNG, available_depth: i64) -> Playout<State>
+   where State: Grammar,
+         RNG: Rng
+{
+   let mut choices = Vec::new();
+   let formula = Formula::empty();
+   let stack = vec![State::root_state()];
+   let (formula, score) = policy_expand(formula, stack, rng, available_depth, |position, rules, rng| {
+                             let state = *position.stack.last().expect("policy_playout: no state to expand.");
+                             let nb_rules = rules.len();
+                             let code = (choices.len(), state);
+                             let rule_index = policy.choose(code, nb_rules, rng);
+                             choices.push(Choice { code, rule_index, nb_rules });
+                             rule_index
+                          });
+   Playout { choices, formula, score }
+}
+
+/// performs a playout following the policy and records its formula in the result
+/// returns None if there was no evaluation left
+fn evaluated_playout<State, RNG, Res>(policy: &Policy<State>,
+                                      rng: &mut RNG,
+                                      available_depth: i64,
+                                      result: &mut Res,
+                                      nb_evaluations_left: &mut usize)
+                                      -> Option<Playout<State>>
+   where State: Grammar,
+         RNG: Rng,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   if *nb_evaluations_left == 0
+   {
+      return None;
+   }
+   *nb_evaluations_left -= 1;
+   let playout = policy_playout(policy, rng, available_depth);
+   result.update(playout.formula.clone(), playout.score);
+   Some(playout)
+}
+
+/// performs a nested rollout policy adaptation search of the given level
+/// a search of a given level performs nb_iterations_per_level searches of the level below,
+/// each starting from the current policy, and adapts the policy towards the best playout found so far
+/// takes one policy per level, the last one being the policy of the search
+/// returns None if there was no evaluation left to find a formula
+/// NOTE: a search of level 0 is a playout following the policy
+///       the policies of the levels below are overwritten at each iteration, which reuses their memory
+pub fn nrpa<State, RNG, Res>(level: usize,
+                             policies: &mut [Policy<State>],
+                             parameters: &NrpaParameters,
+                             rng: &mut RNG,
+                             available_depth: i64,
+                             result: &mut Res,
+                             nb_evaluations_left: &mut usize)
+                             -> Option<Playout<State>>
+   where State: Grammar,
+         State::ScoreType: PartialOrd,
+         RNG: Rng,
+         Res: Result<State, ScoreType = State::ScoreType>
+{
+   let (policy, lower_policies) = policies.split_last_mut().expect("nrpa: one policy per level is needed.");
+   if level == 0
+   {
+      return evaluated_playout(policy, rng, available_depth, result, nb_evaluations_left);
+   }
+
+   let mut best_playout: Option<Playout<State>> = None;
+   for _ in 0..parameters.nb_iterations_per_level
+   {
+      let playout = if level == 1
+      {
+         // a playout does not modify the policy, there is no need for a copy
+         evaluated_playout(policy, rng, available_depth, result, nb_evaluations_left)
+      }
+      else
+      {
+         lower_policies[level - 1].clone_from(policy);
+         nrpa(level - 1, lower_policies, parameters, rng, available_depth, result, nb_evaluations_left)
+      };
+      let playout = match playout
+      {
+         None => break, // no evaluation left
+         Some(playout) => playout
+      };
+      // ties are accepted in order to move along plateaus
+      let is_better = best_playout.as_ref().is_none_or(|best| playout.score >= best.score);
+      if is_better
+      {
+         best_playout = Some(playout);
+      }
+      policy.adapt(&best_playout.as_ref().unwrap().choices, parameters.learning_rate);
+   }
+   best_playout
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+   use crate::result::Single;
+   use super::super::test_grammar::Bits;
+
+   #[test]
+   fn adapts_the_policy_towards_the_choices()
+   {
+      let mut policy = Policy::<Bits>::new();
+      let code = (0, Bits::Remaining(8));
+      let choices = [Choice { code, rule_index: 1, nb_rules: 2 }];
+      policy.adapt(&choices, 1.);
+      // the gradient is computed with the uniform policy
+      assert_eq!(policy.weight(code, 0), -0.5);
+      assert_eq!(policy.weight(code, 1), 0.5);
+      let probabilities = policy.probabilities(code, 2);
+      assert!((probabilities[1] - 1. / (1. + (-1f64).exp())).abs() < 1e-12);
+      // the same state, at another decision, is not affected
+      assert_eq!(policy.probabilities((1, Bits::Remaining(8)), 2), [0.5, 0.5]);
+   }
+
+   #[test]
+   fn learns_the_best_formula()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut result = Single::<Bits>::new();
+      let mut nb_evaluations_left = 1000;
+      let mut policies = vec![Policy::new(); 3];
+      let parameters = NrpaParameters { nb_iterations_per_level: 20, learning_rate: 1. };
+      let playout =
+         nrpa(2, &mut policies, &parameters, &mut rng, 100, &mut result, &mut nb_evaluations_left).unwrap();
+      assert_eq!(nb_evaluations_left, 1000 - 20 * 20);
+      assert_eq!(playout.formula.len(), 8);
+      assert_eq!(playout.choices.len(), 8);
+      assert_eq!(playout.score, 8.);
+      assert_eq!(result.score, 8.);
+   }
+
+   #[test]
+   fn performs_nb_iterations_per_level_searches_per_level()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut result = Single::<Bits>::new();
+      let mut nb_evaluations_left = 1000;
+      let mut policies = vec![Policy::new(); 3];
+      let parameters = NrpaParameters { nb_iterations_per_level: 10, learning_rate: 0.5 };
+      let playout = nrpa(2, &mut policies, &parameters, &mut rng, 3, &mut result, &mut nb_evaluations_left);
+      assert_eq!(nb_evaluations_left, 900);
+      // the depth limits the number of decisions
+      let playout = playout.unwrap();
+      assert_eq!(playout.formula.len(), 8);
+      assert_eq!(playout.choices.len(), 3);
+      // stops once the evaluations are exhausted
+      let mut nb_evaluations_left = 5;
+      let playout = nrpa(2, &mut policies, &parameters, &mut rng, 3, &mut result, &mut nb_evaluations_left);
+      assert!(playout.is_some());
+      assert_eq!(nb_evaluations_left, 0);
+   }
+}
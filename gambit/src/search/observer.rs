@@ -0,0 +1,157 @@
+use crate::grammar::{Grammar, Formula};
+use crate::result::Result;
+use crate::memory::TreeSize;
+use log::info;
+
+/// hooks called by the search functions at key points of the search
+/// useful to monitor a search, checkpoint it or stop it early
+/// NOTE: all callbacks default to doing nothing
+pub trait Observer<State: Grammar>
+{
+   /// called at the start of each iteration, returning false stops the search
+   fn iteration_start(&mut self, _iteration: usize) -> bool
+   {
+      true
+   }
+
+   /// called at the end of each iteration with the formula evaluated during the iteration
+   fn iteration_end(&mut self, _iteration: usize, _formula: &Formula<State>, _score: State::ScoreType) {}
+
+   /// called when the result finds a new best formula
+   fn new_best(&mut self, _iteration: usize, _formula: &Formula<State>, _score: f64) {}
+
+   /// called after the tree has been pruned to reduce its memory use
+   fn tree_pruned(&mut self, _iteration: usize) {}
+
+   /// called when the search stops growing the tree because the memory limit has been reached
+   /// with the size of the tree at that point
+   fn memory_mode_switched(&mut self, _iteration: usize, _tree_size: &TreeSize) {}
+
+   /// called when the search stops because there is no formula left to explore
+   /// with the number of distinct formulas evaluated
+   /// NOTE: searches that stop growing their tree or prune it can evaluate a formula several times,
+   ///       the number of formulas is then an upper bound
+   ///       and, if subtrees were deleted, their formulas might not have been explored
+   fn search_exhausted(&mut self, _iteration: usize, _nb_formulas: usize) {}
+}
+
+/// an observer that does nothing
+pub struct NoObserver;
+
+impl<State: Grammar> Observer<State> for NoObserver {}
+
+/// updates the result with the formula and notifies the observer of the end of the iteration
+/// and of any improvement
+pub fn update_result<State, Res, Obs>(result: &mut Res,
+                                      observer: &mut Obs,
+                                      iteration: usize,
+                                      formula: Formula<State>,
+                                      score: State::ScoreType)
+   where State: Grammar,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Obs: Observer<State>
+{
+   observer.iteration_end(iteration, &formula, score);
+   if result.update(formula, score)
+   {
+      let (formula, score) = result.best();
+      observer.new_best(iteration, &formula, score);
+   }
+}
+
+/// logs that the search space has been exhausted and notifies the result and the observer
+pub fn report_exhaustion<State, Res, Obs>(result: &mut Res,
+                                          observer: &mut Obs,
+                                          iteration: usize,
+                                          nb_formulas: usize)
+   where State: Grammar,
+         Res: Result<State>,
+         Obs: Observer<State>
+{
+   info!(target: "gambit::search",
+         iteration = iteration,
+         nb_formulas = nb_formulas;
+         "search space exhausted");
+   result.search_exhausted(nb_formulas);
+   observer.search_exhausted(iteration, nb_formulas);
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use crate::distribution::ThompsonMax;
+   use crate::memory::MemoryBudget;
+   use crate::result::Single;
+   use crate::search::{memory_limited_search_observed, nested_search_observed};
+   use crate::search::{MctsParameters, NoNormalization, NoWidening, CollapseLeastRecentlyVisited};
+   use super::super::test_grammar::Bits;
+
+   /// counts the calls to the hooks
+   #[derive(Default)]
+   struct RecordHooks
+   {
+      nb_iteration_end: usize,
+      nb_tree_pruned: usize,
+      nb_memory_mode_switched: usize
+   }
+
+   impl Observer<Bits> for RecordHooks
+   {
+      fn iteration_end(&mut self, _iteration: usize, _formula: &Formula<Bits>, _score: f64)
+      {
+         self.nb_iteration_end += 1;
+      }
+
+      fn tree_pruned(&mut self, _iteration: usize)
+      {
+         self.nb_tree_pruned += 1;
+      }
+
+      fn memory_mode_switched(&mut self, _iteration: usize, _tree_size: &TreeSize)
+      {
+         self.nb_memory_mode_switched += 1;
+      }
+   }
+
+   #[test]
+   fn memory_limited_search_notifies_the_switch_of_mode()
+   {
+      let mut observer = RecordHooks::default();
+      memory_limited_search_observed::<Bits,
+                                       ThompsonMax,
+                                       Single<Bits>,
+                                       NoNormalization,
+                                       NoWidening,
+                                       RecordHooks>(8,
+                                                    100,
+                                                    MemoryBudget::Nodes(10),
+                                                    MctsParameters::default(),
+                                                    &mut observer);
+      assert_eq!(observer.nb_iteration_end, 100);
+      assert_eq!(observer.nb_memory_mode_switched, 1);
+      assert_eq!(observer.nb_tree_pruned, 0);
+   }
+
+   #[test]
+   fn nested_search_notifies_the_pruning()
+   {
+      // the limit is above any free memory such that the tree is pruned as soon as possible
+      let free_memory_size = 1 << 40;
+      let mut observer = RecordHooks::default();
+      nested_search_observed::<Bits,
+                               ThompsonMax,
+                               Single<Bits>,
+                               NoNormalization,
+                               CollapseLeastRecentlyVisited,
+                               NoWidening,
+                               RecordHooks>(8,
+                                            100,
+                                            free_memory_size,
+                                            MctsParameters::default(),
+                                            &mut observer);
+      assert_eq!(observer.nb_iteration_end, 100);
+      assert!(observer.nb_tree_pruned > 0);
+      assert_eq!(observer.nb_memory_mode_switched, 0);
+   }
+}
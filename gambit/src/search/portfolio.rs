@@ -0,0 +1,514 @@
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256Plus;
+use float_ord::FloatOrd;
+use crate::distribution::Distribution;
+use crate::grammar::{Grammar, Formula};
+use crate::result::Result;
+use crate::tools::lne;
+use crate::memory::{TreeSize, MemoryBudget, MemoryTracker};
+use log::info;
+use super::tree::*;
+use super::expand::expand;
+use super::no_expand::no_expand;
+use super::normalizer::{Normalizer, NoNormalization};
+use super::widening::{Widening, NoWidening};
+use super::context::Context;
+
+/// default number of iterations given to an arm each time it is selected
+const DEFAULT_ROUND_SIZE: usize = 100;
+
+/// default number of consecutive rounds without improvement after which an arm is restarted
+const DEFAULT_STAGNATION_ROUNDS: usize = 50;
+
+//-----------------------------------------------------------------------------
+// ARM
+
+/// a search that can be run one iteration at a time and restarted
+pub trait Arm<State: Grammar, Res>
+{
+   /// returns a description of the arm
+   fn name(&self) -> String;
+
+   /// runs one iteration of the search, updating the shared result
+   /// returns true if the best score found by the arm (since its creation) improved
+   fn iterate(&mut self, result: &mut Res) -> bool;
+
+   /// discards the tree and starts a new search with a new seed
+   fn restart(&mut self);
+
+   /// returns true if the arm has explored the whole search space and cannot find new formulas
+   /// NOTE: the portfolio stops as soon as one of its arms is exhausted
+   fn is_exhausted(&self) -> bool;
+
+   /// returns the size of the tree stored by the arm
+   /// NOTE: used to enforce the memory budget of the portfolio, arms without a tree are empty
+   fn tree_size(&self) -> TreeSize
+   {
+      TreeSize::default()
+   }
+
+   /// lets the arm grow its tree or asks it to stop growing it (when the memory budget is exhausted)
+   /// NOTE: does nothing by default
+   fn set_growing(&mut self, _growing: bool) {}
+}
+
+/// a monte carlo tree search with a given distribution, normalizer, widening and seed
+struct TreeArm<State: Grammar, Distr: Distribution, Res, Norm, Widen>
+{
+   available_depth: usize,
+   seed: u64,
+   nb_restarts: u64,
+   context: Context<State, Xoshiro256Plus, Norm, Widen>, // iteration since the last restart
+   tree: Tree<Distr>,
+   tree_size: TreeSize,
+   balance_factor: Option<f64>, // set once the tree stops growing (see `no_expand`)
+   exhausted: bool,
+   best: Res // best formulas found by the arm, kept across restarts
+}
+
+impl<State, Distr, Res, Norm, Widen> TreeArm<State, Distr, Res, Norm, Widen>
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Norm: Normalizer<State::ScoreType>
+{
+   fn new(available_depth: usize, seed: u64, widening: Widen) -> Self
+   {
+      let tree = Tree::<Distr>::new();
+      let tree_size = TreeSize::of(&tree);
+      TreeArm { available_depth,
+                seed,
+                nb_restarts: 0,
+                context: Context::new(Xoshiro256Plus::seed_from_u64(seed), Norm::new(), widening),
+                tree,
+                tree_size,
+                balance_factor: None,
+                exhausted: false,
+                best: Res::new() }
+   }
+}
+
+impl<State, Distr, Res, Norm, Widen> Arm<State, Res> for TreeArm<State, Distr, Res, Norm, Widen>
+   where State: Grammar,
+         Distr: Distribution<ScoreType = State::ScoreType>,
+         Res: Result<State, ScoreType = State::ScoreType>,
+         Norm: Normalizer<State::ScoreType>,
+         Widen: Widening
+{
+   fn name(&self) -> String
+   {
+      format!("{}(seed={})", std::any::type_name::<Distr>(), self.seed)
+   }
+
+   fn iterate(&mut self, result: &mut Res) -> bool
+   {
+      let formula = Formula::empty();
+      let stack = vec![State::root_state()];
+      let available_depth = self.available_depth as i64;
+      let (action, formula, score) = match self.balance_factor
+      {
+         None =>
+         {
+            expand(&mut self.tree, formula, stack, &mut self.context, &mut self.tree_size, available_depth)
+         }
+         Some(balance_factor) =>
+         {
+            no_expand(&mut self.tree, formula, stack, &mut self.context, available_depth, balance_factor)
+         }
+      };
+      let iteration = self.context.iteration + 1;
+      self.context.start_iteration(iteration);
+      match action
+      {
+         ReturnType::NewTree(updated_tree) if self.balance_factor.is_some() =>
+         {
+            // `no_expand` does not track the size of the tree, it is measured again
+            self.tree = updated_tree;
+            self.tree_size = TreeSize::of(&self.tree)
+         }
+         ReturnType::NewTree(updated_tree) =>
+         {
+            self.tree_size.replace(&self.tree, &updated_tree);
+            self.tree = updated_tree
+         }
+         ReturnType::DeleteChild => self.exhausted = true,
+         ReturnType::DoNothing => ()
+      }
+      result.update(formula.clone(), score);
+      self.best.update(formula, score)
+   }
+
+   /// NOTE: the new tree is allowed to grow until the portfolio checks its memory budget again
+   fn restart(&mut self)
+   {
+      self.nb_restarts += 1;
+      let seed = self.seed.wrapping_add(self.nb_restarts);
+      self.context.rng = Xoshiro256Plus::seed_from_u64(seed);
+      self.context.start_iteration(0);
+      self.tree = Tree::<Distr>::new();
+      self.tree_size = TreeSize::of(&self.tree);
+      self.balance_factor = None;
+      self.exhausted = false;
+   }
+
+   fn is_exhausted(&self) -> bool
+   {
+      self.exhausted
+   }
+
+   fn tree_size(&self) -> TreeSize
+   {
+      self.tree_size
+   }
+
+   fn set_growing(&mut self, growing: bool)
+   {
+      match (growing, self.balance_factor)
+      {
+         (true, Some(_)) =>
+         {
+            // the leafs visited by `no_expand` were not accounted for
+            self.balance_factor = None;
+            self.tree_size = TreeSize::of(&self.tree)
+         }
+         (false, None) => self.balance_factor = Some(self.tree.balance_factor(self.context.iteration)),
+         _ => ()
+      }
+   }
+}
+
+//-----------------------------------------------------------------------------
+// PORTFOLIO
+
+/// statistics on the rounds played by an arm
+#[derive(Default)]
+struct ArmStatistics
+{
+   nb_rounds: usize,
+   nb_productive_rounds: usize, // rounds during which the best score of the arm improved
+   nb_stagnating_rounds: usize, // consecutive rounds without improvement
+   nb_restarts: usize
+}
+
+impl ArmStatistics
+{
+   /// upper confidence bound on the probability that a round of the arm is productive
+   fn ucb(&self, nb_rounds_total: usize) -> f64
+   {
+      if self.nb_rounds == 0
+      {
+         return std::f64::INFINITY;
+      }
+      let nb_rounds = self.nb_rounds as f64;
+      let mean = (self.nb_productive_rounds as f64) / nb_rounds;
+      mean + (2. * lne(nb_rounds_total as f64) / nb_rounds).sqrt()
+   }
+}
+
+/// returns the index of the arm, that has not been exhausted, with the largest upper confidence bound
+fn select_arm<State, Res>(arms: &[Box<dyn Arm<State, Res>>],
+                          statistics: &[ArmStatistics],
+                          nb_rounds_total: usize)
+                          -> Option<usize>
+   where State: Grammar
+{
+   (0..arms.len()).filter(|&index| !arms[index].is_exhausted())
+                  .max_by_key(|&index| FloatOrd(statistics[index].ucb(nb_rounds_total)))
+}
+
+/// interleaves several searches (arms) under a single budget, sharing a single result
+/// iterations are given, in rounds of round_size, to the arm that improved the most often
+/// and arms that stagnate for stagnation_rounds rounds are restarted with a new seed
+/// NOTE: the memory budget, if any, is shared by the trees of all the arms
+pub struct Portfolio<State: Grammar, Res>
+{
+   available_depth: usize,
+   round_size: usize,
+   stagnation_rounds: usize,
+   budget: Option<MemoryBudget>,
+   arms: Vec<Box<dyn Arm<State, Res>>>
+}
+
+impl<State, Res> Portfolio<State, Res>
+   where State: Grammar + 'static,
+         Res: Result<State, ScoreType = State::ScoreType> + 'static
+{
+   /// creates an empty portfolio
+   pub fn new(available_depth: usize) -> Self
+   {
+      Portfolio { available_depth,
+                  round_size: DEFAULT_ROUND_SIZE,
+                  stagnation_rounds: DEFAULT_STAGNATION_ROUNDS,
+                  budget: None,
+                  arms: Vec::new() }
+   }
+
+   /// sets the number of iterations given to an arm each time it is selected
+   pub fn with_round_size(mut self, round_size: usize) -> Self
+   {
+      self.round_size = round_size.max(1);
+      self
+   }
+
+   /// sets the number of consecutive rounds without improvement after which an arm is restarted
+   pub fn with_stagnation_rounds(mut self, stagnation_rounds: usize) -> Self
+   {
+      self.stagnation_rounds = stagnation_rounds;
+      self
+   }
+
+   /// stops growing the trees of the arms once they have used the given budget
+   /// NOTE: the budget is checked before each round
+   pub fn with_memory_budget(mut self, budget: MemoryBudget) -> Self
+   {
+      self.budget = Some(budget);
+      self
+   }
+
+   /// adds a monte carlo tree search with the given distribution and seed to the portfolio
+   pub fn with_arm<Distr>(self, seed: u64) -> Self
+      where Distr: Distribution<ScoreType = State::ScoreType> + 'static
+   {
+      self.with_tree_arm::<Distr, NoNormalization, NoWidening>(seed, NoWidening)
+   }
+
+   /// adds a monte carlo tree search with the given distribution, normalizer, widening and seed
+   pub fn with_tree_arm<Distr, Norm, Widen>(mut self, seed: u64, widening: Widen) -> Self
+      where Distr: Distribution<ScoreType = State::ScoreType> + 'static,
+            Norm: Normalizer<State::ScoreType> + 'static,
+            Widen: Widening + 'static
+   {
+      let arm = TreeArm::<State, Distr, Res, Norm, Widen>::new(self.available_depth, seed, widening);
+      self.arms.push(Box::new(arm));
+      self
+   }
+
+   /// adds an arbitrary arm to the portfolio
+   pub fn with_custom_arm(mut self, arm: Box<dyn Arm<State, Res>>) -> Self
+   {
+      self.arms.push(arm);
+      self
+   }
+
+   /// performs the search for a given number of iterations (shared between all the arms)
+   /// WARNING: each arm stores its own tree, this function is memory hungry and could fill the RAM
+   pub fn search(mut self, nb_iterations: usize) -> Res
+   {
+      let memory_tracker = MemoryTracker::new();
+      let mut result = Res::new();
+      let mut statistics: Vec<ArmStatistics> = self.arms.iter().map(|_| ArmStatistics::default()).collect();
+      let mut iteration = 0;
+      let mut nb_rounds_total = 0;
+      while iteration < nb_iterations
+      {
+         let arm_index = match select_arm(&self.arms, &statistics, nb_rounds_total)
+         {
+            None => break, // no arm left to run
+            Some(arm_index) => arm_index
+         };
+         // the arm grows its tree only if the trees of all the arms fit in the budget
+         if let Some(budget) = self.budget
+         {
+            let tree_size = self.arms.iter().fold(TreeSize::default(), |total, arm| total + arm.tree_size());
+            let free_memory =
+               if budget.is_system_dependent() { memory_tracker.free_memory() as i64 } else { std::i64::MAX };
+            self.arms[arm_index].set_growing(!budget.is_exhausted(&tree_size, free_memory));
+         }
+         let arm = &mut self.arms[arm_index];
+         let stats = &mut statistics[arm_index];
+
+         // runs a round
+         let mut productive = false;
+         for _ in 0..self.round_size.min(nb_iterations - iteration)
+         {
+            productive |= arm.iterate(&mut result);
+            iteration += 1;
+            if arm.is_exhausted()
+            {
+               break;
+            }
+         }
+         nb_rounds_total += 1;
+         stats.nb_rounds += 1;
+         if productive
+         {
+            stats.nb_productive_rounds += 1;
+            stats.nb_stagnating_rounds = 0;
+         }
+         else
+         {
+            stats.nb_stagnating_rounds += 1;
+         }
+
+         // the result already contains the best formula of the search space
+         if arm.is_exhausted()
+         {
+            info!(target: "gambit::search",
+                  arm:% = arm.name(),
+                  iteration = iteration;
+                  "search space exhausted");
+            break;
+         }
+
+         // restarts stagnating arms
+         if stats.nb_stagnating_rounds >= self.stagnation_rounds
+         {
+            arm.restart();
+            stats.nb_restarts += 1;
+            stats.nb_stagnating_rounds = 0;
+         }
+      }
+
+      for (arm, stats) in self.arms.iter().zip(statistics.iter())
+      {
+         info!(target: "gambit::search",
+               arm:% = arm.name(),
+               nb_rounds = stats.nb_rounds,
+               nb_productive_rounds = stats.nb_productive_rounds,
+               nb_restarts = stats.nb_restarts;
+               "portfolio arm");
+      }
+      result
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use std::rc::Rc;
+   use std::cell::Cell;
+   use crate::distribution::ThompsonMax;
+   use crate::result::Single;
+   use super::super::test_grammar::Bits;
+
+   /// an arm that finds a good formula on its first iteration, and then only if it is productive
+   /// counts its iterations and restarts
+   struct ScriptedArm
+   {
+      productive: bool,
+      nb_iterations: Rc<Cell<usize>>,
+      nb_restarts: Rc<Cell<usize>>
+   }
+
+   impl ScriptedArm
+   {
+      fn new(productive: bool) -> (ScriptedArm, Rc<Cell<usize>>, Rc<Cell<usize>>)
+      {
+         let nb_iterations = Rc::new(Cell::new(0));
+         let nb_restarts = Rc::new(Cell::new(0));
+         let (iterations, restarts) = (nb_iterations.clone(), nb_restarts.clone());
+         let arm = ScriptedArm { productive, nb_iterations: iterations, nb_restarts: restarts };
+         (arm, nb_iterations, nb_restarts)
+      }
+   }
+
+   impl Arm<Bits, Single<Bits>> for ScriptedArm
+   {
+      fn name(&self) -> String
+      {
+         format!("scripted(productive={})", self.productive)
+      }
+
+      fn iterate(&mut self, result: &mut Single<Bits>) -> bool
+      {
+         let improves = self.productive || (self.nb_iterations.get() == 0);
+         self.nb_iterations.set(self.nb_iterations.get() + 1);
+         let mut formula = Formula::empty();
+         formula.push(if improves { Bits::One } else { Bits::Zero });
+         let score = formula.evaluate() + if improves { self.nb_iterations.get() as f64 } else { 0. };
+         result.update(formula, score);
+         improves
+      }
+
+      fn restart(&mut self)
+      {
+         self.nb_restarts.set(self.nb_restarts.get() + 1);
+      }
+
+      fn is_exhausted(&self) -> bool
+      {
+         false
+      }
+   }
+
+   #[test]
+   fn gives_the_iterations_to_the_productive_arm()
+   {
+      let (productive_arm, nb_productive_iterations, _) = ScriptedArm::new(true);
+      let (stagnating_arm, nb_stagnating_iterations, _) = ScriptedArm::new(false);
+      Portfolio::<Bits, Single<Bits>>::new(8).with_round_size(10)
+                                             .with_custom_arm(Box::new(stagnating_arm))
+                                             .with_custom_arm(Box::new(productive_arm))
+                                             .search(10_000);
+      assert_eq!(nb_productive_iterations.get() + nb_stagnating_iterations.get(), 10_000);
+      assert!(nb_productive_iterations.get() > 9 * nb_stagnating_iterations.get());
+   }
+
+   #[test]
+   fn restarts_keep_the_shared_result()
+   {
+      let (arm, nb_iterations, nb_restarts) = ScriptedArm::new(false);
+      let result = Portfolio::<Bits, Single<Bits>>::new(8).with_round_size(1)
+                                                          .with_stagnation_rounds(3)
+                                                          .with_custom_arm(Box::new(arm))
+                                                          .search(100);
+      assert_eq!(nb_iterations.get(), 100);
+      // the first round is productive, then a restart happens every 3 rounds
+      assert_eq!(nb_restarts.get(), 33);
+      // the formula found during the first iteration is kept
+      assert_eq!(result.score, 2.);
+   }
+
+   type BitsArm = TreeArm<Bits, ThompsonMax, Single<Bits>, NoNormalization, NoWidening>;
+
+   #[test]
+   fn tree_arms_restart_after_exhaustion()
+   {
+      // a single decision, the search space is exhausted after two formulas
+      let mut arm = BitsArm::new(1, 0, NoWidening);
+      let mut result = Single::new();
+      for _ in 0..10
+      {
+         if arm.is_exhausted()
+         {
+            break;
+         }
+         arm.iterate(&mut result);
+      }
+      assert!(arm.is_exhausted());
+      assert_eq!(result.score, 1.);
+      arm.restart();
+      assert!(!arm.is_exhausted());
+      assert_eq!(arm.tree_size(), TreeSize::of(&Tree::<ThompsonMax>::new()));
+   }
+
+   #[test]
+   fn tree_arms_stop_growing_outside_of_the_budget()
+   {
+      let mut arm = BitsArm::new(8, 0, NoWidening);
+      let mut result = Single::new();
+      for _ in 0..10
+      {
+         arm.iterate(&mut result);
+      }
+      arm.set_growing(false);
+      let tree_size = arm.tree_size();
+      for _ in 0..100
+      {
+         arm.iterate(&mut result);
+      }
+      // `no_expand` can collapse nodes but never creates new ones
+      let frozen_size = TreeSize::of(&arm.tree);
+      assert!(frozen_size.nb_nodes <= tree_size.nb_nodes);
+      // the tree is measured again once it grows
+      arm.set_growing(true);
+      assert_eq!(arm.tree_size(), frozen_size);
+      for _ in 0..10
+      {
+         arm.iterate(&mut result);
+      }
+      assert!(arm.tree_size().nb_nodes > frozen_size.nb_nodes);
+      assert_eq!(arm.tree_size(), TreeSize::of(&arm.tree));
+   }
+}
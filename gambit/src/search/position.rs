@@ -0,0 +1,58 @@
+use crate::grammar::{Grammar, Formula};
+
+/// a partially expanded formula
+#[derive(Clone)]
+pub struct Position<State: Grammar>
+{
+   pub formula: Formula<State>,
+   pub stack: Vec<State>, // states still to be expanded, the last one being expanded next
+   pub available_depth: i64
+}
+
+impl<State: Grammar> Position<State>
+{
+   /// returns the position at the root of the grammar
+   pub fn root(available_depth: usize) -> Position<State>
+   {
+      Position { formula: Formula::empty(),
+                 stack: vec![State::root_state()],
+                 available_depth: available_depth as i64 }
+   }
+
+   /// applies all the expansions that do not require a decision
+   /// returns the rules of the next decision or None if the formula is complete
+   /// NOTE: once there is no depth left, the first rule is always taken (it leads to the shortest formula)
+   pub fn next_decision(&mut self) -> Option<Vec<Vec<State>>>
+   {
+      while let Some(state) = self.stack.pop()
+      {
+         let mut rules = state.expand();
+         match rules.len()
+         {
+            0 => self.formula.push(state),
+            1 => self.stack.extend(rules.pop().unwrap()),
+            _ if self.available_depth <= 0 =>
+            {
+               // no more depth available to make decisions
+               self.available_depth -= 1;
+               self.stack.extend(rules.swap_remove(0));
+            }
+            _ =>
+            {
+               // puts the state back such that the decision can be played later
+               self.stack.push(state);
+               return Some(rules);
+            }
+         }
+      }
+      None
+   }
+
+   /// plays a rule at the current decision
+   pub fn play(&mut self, rule: &[State])
+   {
+      self.stack.pop();
+      self.available_depth -= 1;
+      self.stack.extend(rule);
+   }
+}
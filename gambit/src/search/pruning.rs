@@ -0,0 +1,360 @@
+use rand::Rng;
+use float_ord::FloatOrd;
+use crate::distribution::Distribution;
+use crate::memory::TreeSize;
+use super::tree::*;
+
+//-----------------------------------------------------------------------------
+// TRAIT
+
+/// a strategy to reduce the memory used by a tree
+pub trait Pruning
+{
+   /// prunes subtrees in order to free (at least) the given number of bytes
+   /// returns the number of bytes actually freed which can be lower if nothing else can be pruned
+   fn prune<Distr: Distribution, RNG: Rng>(tree: &mut Tree<Distr>, nb_bytes: usize, rng: &mut RNG) -> usize;
+}
+
+/// deletes the subtrees that have not been visited for the longest time
+pub struct PruneLeastRecentlyVisited;
+
+/// deletes the subtrees with the lowest score (as used to select children during the search)
+pub struct PruneLowestScore;
+
+/// replaces the subtrees that have not been visited for the longest time with known leafs
+/// their statistics are kept such that they can be expanded again later
+pub struct CollapseLeastRecentlyVisited;
+
+//-----------------------------------------------------------------------------
+// TYPES
+
+/// what should be done to a pruned subtree
+#[derive(Clone, Copy, PartialEq)]
+enum Action
+{
+   Delete,  // the subtree is deleted and will never be explored again
+   Collapse // the subtree is replaced with a known leaf
+}
+
+/// a subtree that could be pruned
+struct Candidate
+{
+   key: f64,             // the candidates with the lowest keys are pruned first
+   nb_bytes: usize,      // memory used by the subtree, minus the memory used by the candidates inside it
+   nb_descendants: usize // number of candidates inside the subtree
+}
+
+//-----------------------------------------------------------------------------
+// FUNCTIONS
+
+/// returns true if the given child of a node could be pruned with the given action
+/// the first child is never deleted as, by convention, it is on the shortest path to a valid formula
+fn is_candidate<Distr: Distribution>(child: &Tree<Distr>, rule_index: usize, action: Action) -> bool
+{
+   match child
+   {
+      Tree::Node(_) => (action == Action::Collapse) || (rule_index != 0),
+      _ => false
+   }
+}
+
+/// returns the iteration of the last visit to the node, 0 if the distribution does not record it
+/// NOTE: the distribution needs to be wrapped in a `Timestamped` for the visits to be recorded
+fn last_visit<Distr: Distribution>(node: &Node<Distr>) -> f64
+{
+   node.distribution.last_visit().unwrap_or(0) as f64
+}
+
+/// pushes all the candidates of the tree in the vector, in depth first order
+/// returns the memory used by the tree and the memory used by the outermost candidates it contains
+/// NOTE: the memory of a candidate excludes the memory of the candidates it contains
+///       such that the memory freed by pruning a set of nested candidates is not counted twice
+fn collect_candidates<Distr, RNG, Key>(tree: &Tree<Distr>,
+                                       action: Action,
+                                       key: &mut Key,
+                                       rng: &mut RNG,
+                                       candidates: &mut Vec<Candidate>)
+                                       -> (usize, usize)
+   where Distr: Distribution,
+         RNG: Rng,
+         Key: FnMut(&Node<Distr>, &Node<Distr>, &mut RNG) -> f64
+{
+   let mut nb_bytes = TreeSize::of_element(tree).nb_bytes;
+   let mut nb_bytes_candidates = 0;
+   if let Tree::Node(box node) = tree
+   {
+      for (rule_index, child) in node.children.visited()
+      {
+         match child
+         {
+            Tree::Node(box child_node) if is_candidate(child, rule_index, action) =>
+            {
+               let position = candidates.len();
+               candidates.push(Candidate { key: key(child_node, node, rng), nb_bytes: 0, nb_descendants: 0 });
+               let (nb_bytes_child, nb_bytes_inner_candidates) =
+                  collect_candidates(child, action, key, rng, candidates);
+               candidates[position].nb_bytes = nb_bytes_child - nb_bytes_inner_candidates;
+               candidates[position].nb_descendants = candidates.len() - position - 1;
+               nb_bytes += nb_bytes_child;
+               nb_bytes_candidates += nb_bytes_child;
+            }
+            _ =>
+            {
+               let (nb_bytes_child, nb_bytes_inner_candidates) =
+                  collect_candidates(child, action, key, rng, candidates);
+               nb_bytes += nb_bytes_child;
+               nb_bytes_candidates += nb_bytes_inner_candidates;
+            }
+         }
+      }
+   }
+   (nb_bytes, nb_bytes_candidates)
+}
+
+/// returns the key under which candidates should be pruned to free the given number of bytes
+fn compute_threshold(candidates: &[Candidate], nb_bytes: usize) -> f64
+{
+   let mut sorted_candidates: Vec<&Candidate> = candidates.iter().collect();
+   sorted_candidates.sort_by_key(|candidate| FloatOrd(candidate.key));
+   let mut total_bytes = 0;
+   for candidate in sorted_candidates
+   {
+      total_bytes += candidate.nb_bytes;
+      if total_bytes >= nb_bytes
+      {
+         return candidate.key;
+      }
+   }
+   std::f64::INFINITY
+}
+
+/// prunes the candidates whose key is lower or equal to the threshold
+/// the candidates are consumed in the same order as they were collected
+/// returns the number of bytes freed
+fn prune_candidates<Distr, Candidates>(tree: &mut Tree<Distr>,
+                                       action: Action,
+                                       threshold: f64,
+                                       candidates: &mut Candidates)
+                                       -> usize
+   where Distr: Distribution,
+         Candidates: Iterator<Item = Candidate>
+{
+   let mut nb_bytes_freed = 0;
+   if let Tree::Node(box Node { children, .. }) = tree
+   {
+      // the children are modified while iterating, we thus iterate on their rule indexes
+      let rule_indexes: Vec<usize> = children.visited().map(|(rule_index, _)| rule_index).collect();
+      for rule_index in rule_indexes
+      {
+         let child = children.get_mut(rule_index).expect("prune_candidates: a visited child disappeared.");
+         if !is_candidate(child, rule_index, action)
+         {
+            nb_bytes_freed += prune_candidates(child, action, threshold, candidates);
+            continue;
+         }
+         let candidate = candidates.next().expect("prune_candidates: the candidates do not match the tree.");
+         // a node cannot lose all of its children
+         let nb_children_left = children.visited().count() + children.nb_untried();
+         let prunable = (action == Action::Collapse) || (nb_children_left > 1);
+         if (candidate.key <= threshold) && prunable
+         {
+            let child = children.get(rule_index).expect("prune_candidates: a visited child disappeared.");
+            let nb_bytes = TreeSize::of(child).nb_bytes;
+            let new_child = match (action, child)
+            {
+               (Action::Collapse, Tree::Node(box node)) =>
+               {
+                  // keeps the statistics of the node
+                  Tree::KnownLeaf(Box::new(node.distribution.clone()))
+               }
+               _ => Tree::Deleted
+            };
+            nb_bytes_freed += nb_bytes - TreeSize::of(&new_child).nb_bytes;
+            children.replace(rule_index, new_child);
+            // skips the candidates inside the pruned subtree
+            if candidate.nb_descendants > 0
+            {
+               candidates.nth(candidate.nb_descendants - 1);
+            }
+         }
+         else
+         {
+            let child = children.get_mut(rule_index).expect("prune_candidates: a visited child disappeared.");
+            nb_bytes_freed += prune_candidates(child, action, threshold, candidates);
+         }
+      }
+   }
+   nb_bytes_freed
+}
+
+/// prunes the subtrees with the lowest keys until the given number of bytes has been freed
+/// the key of a subtree is computed from its node and the node of its father
+/// returns the number of bytes freed
+fn prune_lowest_keys<Distr, RNG, Key>(tree: &mut Tree<Distr>,
+                                      nb_bytes: usize,
+                                      action: Action,
+                                      rng: &mut RNG,
+                                      mut key: Key)
+                                      -> usize
+   where Distr: Distribution,
+         RNG: Rng,
+         Key: FnMut(&Node<Distr>, &Node<Distr>, &mut RNG) -> f64
+{
+   let mut nb_bytes_freed = 0;
+   while nb_bytes_freed < nb_bytes
+   {
+      let mut candidates = Vec::new();
+      collect_candidates(tree, action, &mut key, rng, &mut candidates);
+      let threshold = compute_threshold(&candidates, nb_bytes - nb_bytes_freed);
+      let nb_bytes_pruned = prune_candidates(tree, action, threshold, &mut candidates.into_iter());
+      if nb_bytes_pruned == 0
+      {
+         // nothing left to prune
+         break;
+      }
+      nb_bytes_freed += nb_bytes_pruned;
+   }
+   nb_bytes_freed
+}
+
+//-----------------------------------------------------------------------------
+// TRAIT IMPLEMENTATIONS
+
+impl Pruning for PruneLeastRecentlyVisited
+{
+   fn prune<Distr: Distribution, RNG: Rng>(tree: &mut Tree<Distr>, nb_bytes: usize, rng: &mut RNG) -> usize
+   {
+      prune_lowest_keys(tree, nb_bytes, Action::Delete, rng, |node, _, _| last_visit(node))
+   }
+}
+
+impl Pruning for PruneLowestScore
+{
+   fn prune<Distr: Distribution, RNG: Rng>(tree: &mut Tree<Distr>, nb_bytes: usize, rng: &mut RNG) -> usize
+   {
+      prune_lowest_keys(tree, nb_bytes, Action::Delete, rng, |node, father, rng| {
+         node.distribution.score(&father.distribution, rng)
+      })
+   }
+}
+
+impl Pruning for CollapseLeastRecentlyVisited
+{
+   fn prune<Distr: Distribution, RNG: Rng>(tree: &mut Tree<Distr>, nb_bytes: usize, rng: &mut RNG) -> usize
+   {
+      prune_lowest_keys(tree, nb_bytes, Action::Collapse, rng, |node, _, _| last_visit(node))
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+   use crate::distribution::{Timestamped, UcbTuned};
+
+   type Distr = Timestamped<UcbTuned>;
+
+   /// returns a distribution last visited at the given iteration and updated with the given scores
+   fn distribution(last_visit: usize, scores: &[f64]) -> Distr
+   {
+      let mut distribution = Distr::new();
+      distribution.visit(last_visit);
+      for &score in scores
+      {
+         distribution.update(score);
+      }
+      distribution
+   }
+
+   /// returns a node with one child per distribution, each child being a node with two unexplored rules
+   fn tree(root: Distr, children: Vec<Distr>) -> Tree<Distr>
+   {
+      let mut node = Node { distribution: root, children: Children::new(children.len()) };
+      for (rule_index, distribution) in children.into_iter().enumerate()
+      {
+         let child = Node { distribution, children: Children::new(2) };
+         node.children.replace(rule_index, Tree::Node(Box::new(child)));
+      }
+      Tree::Node(Box::new(node))
+   }
+
+   /// returns the children of the root of the tree
+   fn root_children(tree: &Tree<Distr>) -> &Children<Distr>
+   {
+      match tree
+      {
+         Tree::Node(box node) => &node.children,
+         _ => panic!("the root is not a node")
+      }
+   }
+
+   #[test]
+   fn prunes_the_least_recently_visited_subtree()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let distributions = [0, 5, 1, 9].iter().map(|&stamp| distribution(stamp, &[])).collect();
+      let mut tree = tree(distribution(10, &[]), distributions);
+      let nb_bytes = TreeSize::of(&tree).nb_bytes;
+      let nb_bytes_freed = PruneLeastRecentlyVisited::prune(&mut tree, 1, &mut rng);
+      assert_eq!(nb_bytes_freed, nb_bytes - TreeSize::of(&tree).nb_bytes);
+      let children = root_children(&tree);
+      // the first rule is never deleted even though it is the oldest
+      let visited: Vec<usize> = children.visited().map(|(rule_index, _)| rule_index).collect();
+      assert_eq!(visited, [0, 1, 3]);
+      assert!(!children.is_untried(2));
+   }
+
+   #[test]
+   fn prunes_the_subtree_with_the_lowest_score()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let means = [-1., 1., 0., 2.];
+      let distributions = means.iter().map(|&mean| distribution(0, &[mean; 10])).collect();
+      let mut tree = tree(distribution(0, &[0.; 40]), distributions);
+      PruneLowestScore::prune(&mut tree, 1, &mut rng);
+      let visited: Vec<usize> = root_children(&tree).visited().map(|(rule_index, _)| rule_index).collect();
+      assert_eq!(visited, [0, 1, 3]);
+   }
+
+   #[test]
+   fn collapses_the_least_recently_visited_subtree()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let distributions = [3, 5, 1, 9].iter().map(|&stamp| distribution(stamp, &[1., 2.])).collect();
+      let mut tree = tree(distribution(10, &[]), distributions);
+      CollapseLeastRecentlyVisited::prune(&mut tree, 1, &mut rng);
+      match root_children(&tree).get(2)
+      {
+         // the statistics of the node are kept
+         Some(Tree::KnownLeaf(box distribution)) =>
+         {
+            assert_eq!(distribution.nb_visit(), 2);
+            assert_eq!(distribution.last_visit(), Some(1));
+         }
+         _ => panic!("the least recently visited subtree was not collapsed")
+      }
+      let is_node = |child: &Tree<Distr>| matches!(child, Tree::Node(_));
+      assert!(root_children(&tree).visited().all(|(rule_index, child)| (rule_index == 2) ^ is_node(child)));
+   }
+
+   #[test]
+   fn frees_less_than_requested_when_nothing_is_left_to_prune()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let new_tree = || tree(distribution(10, &[]), (0..4).map(|stamp| distribution(stamp, &[])).collect());
+      // the first rule is kept when deleting
+      let mut tree = new_tree();
+      let nb_bytes = TreeSize::of(&tree).nb_bytes;
+      let nb_bytes_freed = PruneLeastRecentlyVisited::prune(&mut tree, nb_bytes, &mut rng);
+      assert!(nb_bytes_freed < nb_bytes);
+      assert_eq!(root_children(&tree).visited().count(), 1);
+      assert!(matches!(root_children(&tree).get(0), Some(Tree::Node(_))));
+      // every subtree is collapsed
+      let mut tree = new_tree();
+      let nb_bytes_freed = CollapseLeastRecentlyVisited::prune(&mut tree, nb_bytes, &mut rng);
+      assert!(nb_bytes_freed < nb_bytes);
+      assert!(root_children(&tree).visited().all(|(_, child)| matches!(child, Tree::KnownLeaf(_))));
+   }
+}
@@ -1,55 +1,40 @@
 use rand::Rng;
-use rand::seq::SliceRandom;
 use crate::grammar::{Grammar, Formula};
+use super::position::Position;
 
 /// takes a stack and a formula and randomly expands it until we reach a complete formula
 /// avoids useless intermediate structures and tests
-pub fn random_expand<State, RNG>(mut formula: Formula<State>,
-                                 mut stack: Vec<State>,
+pub fn random_expand<State, RNG>(formula: Formula<State>,
+                                 stack: Vec<State>,
                                  rng: &mut RNG,
-                                 mut available_depth: i64)
+                                 available_depth: i64)
                                  -> (Formula<State>, State::ScoreType)
    where State: Grammar,
          RNG: Rng
 {
-   loop
+   policy_expand(formula, stack, rng, available_depth, |_, rules, rng| rng.gen_range(0, rules.len()))
+}
+
+/// takes a stack and a formula and expands it until we reach a complete formula
+/// the index of the rule picked at each decision is given by `choose(position, rules, rng)`
+/// NOTE: a decision is a state with several rules while there is still depth available (see `Position`)
+///       the state being expanded is the last state of the stack of the position
+pub fn policy_expand<State, RNG, Choose>(formula: Formula<State>,
+                                         stack: Vec<State>,
+                                         rng: &mut RNG,
+                                         available_depth: i64,
+                                         mut choose: Choose)
+                                         -> (Formula<State>, State::ScoreType)
+   where State: Grammar,
+         RNG: Rng,
+         Choose: FnMut(&Position<State>, &[Vec<State>], &mut RNG) -> usize
+{
+   let mut position = Position { formula, stack, available_depth };
+   while let Some(rules) = position.next_decision()
    {
-      match stack.pop()
-      {
-         None =>
-         {
-            let score = formula.evaluate();
-            return (formula, score);
-         }
-         Some(state) =>
-         {
-            match state.expand().as_slice()
-            {
-               [] =>
-               {
-                  // terminal state
-                  formula.push(state);
-               }
-               [rule] =>
-               {
-                  // single rule, we can focus on it
-                  stack.extend(rule);
-               }
-               [rule, ..] if available_depth <= 0 =>
-               {
-                  // no more depth available to make decisions
-                  available_depth -= 1;
-                  stack.extend(rule);
-               }
-               rules =>
-               {
-                  // non terminal state, it costs a node
-                  available_depth -= 1;
-                  let rule = rules.choose(rng).unwrap();
-                  stack.extend(rule);
-               }
-            }
-         }
-      }
+      let rule_index = choose(&position, &rules, rng);
+      position.play(&rules[rule_index]);
    }
-}
\ No newline at end of file
+   let score = position.formula.evaluate();
+   (position.formula, score)
+}
@@ -0,0 +1,66 @@
+use rand::Rng;
+use crate::grammar::{Grammar, Formula};
+use super::random_expand::policy_expand;
+
+//-----------------------------------------------------------------------------
+// TRAIT
+
+/// picks the rules used to complete a partial formula during a rollout
+/// (the searches of level 0 of `nested_monte_carlo_search` and the estimations of `beam_search`)
+/// the stack contains the states that are still to be expanded, the last one being expanded next
+pub trait Rollout<State: Grammar>
+{
+   /// called before completing a partial formula
+   /// NOTE: does nothing by default
+   fn start<RNG: Rng>(&mut self, _formula: &Formula<State>, _stack: &[State], _rng: &mut RNG) {}
+
+   /// returns the index of the rule used to expand the last state of the stack
+   fn choose<RNG: Rng>(&mut self,
+                       formula: &Formula<State>,
+                       stack: &[State],
+                       rules: &[Vec<State>],
+                       rng: &mut RNG)
+                       -> usize;
+}
+
+/// picks the rules uniformly (see `random_expand`)
+/// NOTE: this is biased toward short formulas (see `UniformRollout` for an alternative)
+pub struct RandomRollout;
+
+impl<State: Grammar> Rollout<State> for RandomRollout
+{
+   fn choose<RNG: Rng>(&mut self,
+                       _formula: &Formula<State>,
+                       _stack: &[State],
+                       rules: &[Vec<State>],
+                       rng: &mut RNG)
+                       -> usize
+   {
+      rng.gen_range(0, rules.len())
+   }
+}
+
+//-----------------------------------------------------------------------------
+// FUNCTION
+
+/// takes a stack and a formula and expands it, following the rollout, until we reach a complete formula
+/// `record(rule_index)` is called at each decision (see `policy_expand`)
+pub fn rollout_expand<State, RNG, Roll, Record>(formula: Formula<State>,
+                                                stack: Vec<State>,
+                                                rng: &mut RNG,
+                                                available_depth: i64,
+                                                rollout: &mut Roll,
+                                                mut record: Record)
+                                                -> (Formula<State>, State::ScoreType)
+   where State: Grammar,
+         RNG: Rng,
+         Roll: Rollout<State>,
+         Record: FnMut(usize)
+{
+   rollout.start(&formula, &stack, rng);
+   policy_expand(formula, stack, rng, available_depth, |position, rules, rng| {
+      let rule_index = rollout.choose(&position.formula, &position.stack, rules, rng);
+      record(rule_index);
+      rule_index
+   })
+}
@@ -0,0 +1,530 @@
+use rand::Rng;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::grammar::{Grammar, Formula};
+use super::rollout::Rollout;
+
+/// multiplies two counts, a count of zero cancelling an infinite (overflowing) count
+fn multiply(count1: f64, count2: f64) -> f64
+{
+   if (count1 == 0.) || (count2 == 0.)
+   {
+      0.
+   }
+   else
+   {
+      count1 * count2
+   }
+}
+
+/// returns an index with a probability proportional to its weight
+/// NOTE: if some weights are infinite (overflowing counts), one of them is picked uniformly
+fn pick<RNG: Rng>(weights: &[f64], rng: &mut RNG) -> usize
+{
+   let total: f64 = weights.iter().sum();
+   if total.is_infinite()
+   {
+      let infinite_indexes: Vec<usize> =
+         (0..weights.len()).filter(|&index| weights[index].is_infinite()).collect();
+      return infinite_indexes[rng.gen_range(0, infinite_indexes.len())];
+   }
+   let mut target = rng.gen::<f64>() * total;
+   for (index, weight) in weights.iter().enumerate()
+   {
+      target -= weight;
+      if target < 0.
+      {
+         return index;
+      }
+   }
+   // rounding errors
+   weights.iter().rposition(|&weight| weight > 0.).unwrap_or(0)
+}
+
+/// prefix counts of a sequence of states (see `count_prefixes`), shared by the memoization and the samples
+type Prefixes = Rc<Vec<Vec<f64>>>;
+
+/// samples formulas uniformly among the derivations that produce a given number of states
+/// the number of derivations of each state is counted once and then reused by all the samples
+/// NOTE: the counts are stored as floats as they grow exponentially with the size (overflowing to infinity)
+///       states are assumed to produce at least one state (derivations producing no state are ignored)
+///       derivations looping through a cycle of single state rules (A -> B -> A) are not counted
+pub struct SizeSampler<State: Grammar>
+{
+   max_size: usize,
+   counts: HashMap<(State, usize), f64>, // derivations of a state that produce a given number of states
+   prefixes: HashMap<(State, usize, usize), Prefixes> // prefix counts of the rules of a state
+}
+
+impl<State: Grammar> SizeSampler<State>
+{
+   /// creates a sampler and counts the formulas derived from the root of the grammar up to max_size states
+   pub fn new(max_size: usize) -> SizeSampler<State>
+   {
+      let mut sampler = SizeSampler { max_size, counts: HashMap::new(), prefixes: HashMap::new() };
+      for size in 1..=max_size
+      {
+         sampler.count(State::root_state(), size);
+      }
+      sampler
+   }
+
+   /// returns the number of formulas, derived from the root of the grammar, that contain exactly size states
+   pub fn nb_formulas(&mut self, size: usize) -> f64
+   {
+      self.count(State::root_state(), size)
+   }
+
+   /// number of derivations of the state that produce exactly size states
+   fn count(&mut self, state: State, size: usize) -> f64
+   {
+      if let Some(&count) = self.counts.get(&(state, size))
+      {
+         return count;
+      }
+      // marks the count as being computed, a cycle of single state rules will find no derivation
+      self.counts.insert((state, size), 0.);
+      let rules = state.expand();
+      let count = if rules.is_empty()
+      {
+         if size == 1 { 1. } else { 0. }
+      }
+      else
+      {
+         rules.iter()
+              .enumerate()
+              .map(|(rule_index, rule)| self.rule_prefixes(state, rule_index, rule, size)[rule.len()][size])
+              .sum()
+      };
+      self.counts.insert((state, size), count);
+      count
+   }
+
+   /// prefix counts (see `count_prefixes`) of a rule of the state, memoized as they are needed at each sample
+   fn rule_prefixes(&mut self,
+                    state: State,
+                    rule_index: usize,
+                    rule: &[State],
+                    size: usize)
+                    -> Prefixes
+   {
+      if let Some(prefixes) = self.prefixes.get(&(state, rule_index, size))
+      {
+         return prefixes.clone();
+      }
+      let prefixes = Rc::new(self.count_prefixes(rule, size));
+      self.prefixes.insert((state, rule_index, size), prefixes.clone());
+      prefixes
+   }
+
+   /// returns, for each prefix of the sequence of states (from the empty prefix to the full sequence),
+   /// the number of derivations that produce each number of states (from 0 to size)
+   /// NOTE: only the sizes that leave at least one state to each of the following states are counted
+   fn count_prefixes(&mut self, states: &[State], size: usize) -> Vec<Vec<f64>>
+   {
+      let mut prefixes = vec![vec![0.; size + 1]; states.len() + 1];
+      prefixes[0][0] = 1.;
+      if size < states.len()
+      {
+         // each state produces at least one state
+         return prefixes;
+      }
+      for (index, &state) in states.iter().enumerate()
+      {
+         let nb_states_after = states.len() - index - 1;
+         for total in (index + 1)..=(size - nb_states_after)
+         {
+            let previous = &prefixes[index];
+            // the sizes that leave no derivation to the previous states are skipped without being counted
+            let count = (1..=(total - index)).filter(|&size_state| previous[total - size_state] != 0.)
+                                              .map(|size_state| {
+                                                 let count_state = self.count(state, size_state);
+                                                 multiply(count_state, previous[total - size_state])
+                                              })
+                                              .sum();
+            prefixes[index + 1][total] = count;
+         }
+      }
+      prefixes
+   }
+
+   /// expands the state into a random derivation that produces exactly size states
+   fn sample_state<RNG: Rng>(&mut self,
+                             state: State,
+                             size: usize,
+                             formula: &mut Formula<State>,
+                             rng: &mut RNG)
+   {
+      let rules = state.expand();
+      if rules.is_empty()
+      {
+         formula.push(state);
+         return;
+      }
+      let prefixes: Vec<_> = rules.iter()
+                                  .enumerate()
+                                  .map(|(rule_index, rule)| self.rule_prefixes(state, rule_index, rule, size))
+                                  .collect();
+      let weights: Vec<f64> =
+         rules.iter().zip(prefixes.iter()).map(|(rule, prefixes)| prefixes[rule.len()][size]).collect();
+      let rule_index = pick(&weights, rng);
+      self.sample_sequence(&rules[rule_index], size, &prefixes[rule_index], formula, rng);
+   }
+
+   /// expands the sequence of states into a random derivation that produces exactly size states
+   /// takes the prefix counts of the sequence (see `count_prefixes`)
+   /// NOTE: the last state is expanded first, as with a stack
+   fn sample_sequence<RNG: Rng>(&mut self,
+                                states: &[State],
+                                size: usize,
+                                prefixes: &[Vec<f64>],
+                                formula: &mut Formula<State>,
+                                rng: &mut RNG)
+   {
+      let mut size_left = size;
+      for (index, &state) in states.iter().enumerate().rev()
+      {
+         // splits the size left between the state and the states before it
+         let before = &prefixes[index];
+         let mut weights = Vec::with_capacity(size_left - index);
+         for size_state in 1..=(size_left - index)
+         {
+            weights.push(multiply(self.count(state, size_state), before[size_left - size_state]));
+         }
+         let size_state = 1 + pick(&weights, rng);
+         self.sample_state(state, size_state, formula, rng);
+         size_left -= size_state;
+      }
+   }
+
+   /// completes a partial formula
+   /// picked uniformly among the derivations of its stack that add exactly size states to the formula
+   /// returns None if the stack cannot produce size states
+   /// NOTE: the stack contains the states that are still to be expanded, the last one being expanded next
+   ///       this can be used to perform unbiased rollouts
+   pub fn complete<RNG: Rng>(&mut self,
+                             mut formula: Formula<State>,
+                             stack: &[State],
+                             size: usize,
+                             rng: &mut RNG)
+                             -> Option<Formula<State>>
+   {
+      let prefixes = self.count_prefixes(stack, size);
+      if prefixes[stack.len()][size] == 0.
+      {
+         return None;
+      }
+      self.sample_sequence(stack, size, &prefixes, &mut formula, rng);
+      Some(formula)
+   }
+
+   /// returns the number of derivations of the stack that add each number of states (from 0 to size)
+   pub fn nb_completions(&mut self, stack: &[State], size: usize) -> Vec<f64>
+   {
+      self.count_prefixes(stack, size).pop().expect("nb_completions: there is always an empty prefix.")
+   }
+
+   /// returns a weight for each of the rules of the last state of the stack
+   /// such that, picking rules with probabilities proportional to their weights at each decision,
+   /// the stack is completed uniformly among the derivations that add exactly size states
+   pub fn rule_weights(&mut self, stack: &[State], rules: &[Vec<State>], size: usize) -> Vec<f64>
+   {
+      let (&state, others) = stack.split_last().expect("rule_weights: the stack is empty.");
+      let nb_completions_others = self.nb_completions(others, size);
+      rules.iter()
+           .enumerate()
+           .map(|(rule_index, rule)| {
+              let nb_completions_rule = &self.rule_prefixes(state, rule_index, rule, size)[rule.len()];
+              nb_completions_rule.iter()
+                                 .zip(nb_completions_others.iter().rev())
+                                 .map(|(&nb_completions_rule, &nb_completions_others)| {
+                                    multiply(nb_completions_rule, nb_completions_others)
+                                 })
+                                 .sum()
+           })
+           .collect()
+   }
+
+   /// returns a formula picked uniformly among the formulas that contain exactly size states
+   /// returns None if there is no such formula
+   pub fn sample<RNG: Rng>(&mut self, size: usize, rng: &mut RNG) -> Option<Formula<State>>
+   {
+      self.complete(Formula::empty(), &[State::root_state()], size, rng)
+   }
+
+   /// returns a formula with at most max_size states
+   /// picked with a probability proportional to parameter^size (Boltzmann distribution truncated at max_size)
+   /// returns None if there is no formula with at most max_size states
+   /// NOTE: a parameter of 1 is uniform over all the formulas, lower values favour shorter formulas
+   ///       the weights are computed in log space, the counts can overflow while parameter^size underflows
+   pub fn sample_boltzmann<RNG: Rng>(&mut self, parameter: f64, rng: &mut RNG) -> Option<Formula<State>>
+   {
+      let log_weights: Vec<f64> =
+         (1..=self.max_size).map(|size| {
+                               // infinite counts are saturated to stay comparable with the other sizes
+                               let nb_formulas = self.nb_formulas(size).min(std::f64::MAX);
+                               nb_formulas.ln() + (size as f64) * parameter.ln()
+                            })
+                            .collect();
+      let max_log_weight = log_weights.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max);
+      if max_log_weight == std::f64::NEG_INFINITY
+      {
+         return None;
+      }
+      let weights: Vec<f64> =
+         log_weights.iter().map(|log_weight| (log_weight - max_log_weight).exp()).collect();
+      let size = 1 + pick(&weights, rng);
+      self.sample(size, rng)
+   }
+}
+
+//-----------------------------------------------------------------------------
+// ROLLOUT
+
+/// completes the formulas uniformly by size (see `SizeSampler`)
+/// the number of states added is picked uniformly among the numbers that the stack can produce,
+/// within max_size states in total, then the completion is picked uniformly among the ones of that size
+/// the stack is completed with random rules if it cannot produce a formula within max_size states
+/// NOTE: once there is no depth left, the first rule is played instead (see `Position`)
+///       the completions are then only approximately uniform
+pub struct UniformRollout<State: Grammar>
+{
+   sampler: SizeSampler<State>,
+   target_size: Option<usize> // number of states of the formula being completed, None if out of reach
+}
+
+impl<State: Grammar> UniformRollout<State>
+{
+   /// creates a rollout producing formulas with at most max_size states
+   pub fn new(max_size: usize) -> UniformRollout<State>
+   {
+      UniformRollout { sampler: SizeSampler::new(max_size), target_size: None }
+   }
+}
+
+impl<State: Grammar> Rollout<State> for UniformRollout<State>
+{
+   /// picks the size of the formula
+   fn start<RNG: Rng>(&mut self, formula: &Formula<State>, stack: &[State], rng: &mut RNG)
+   {
+      let max_size_added = self.sampler.max_size.saturating_sub(formula.len());
+      let sizes_added: Vec<usize> = self.sampler
+                                        .nb_completions(stack, max_size_added)
+                                        .iter()
+                                        .enumerate()
+                                        .filter(|&(_, &nb_completions)| nb_completions > 0.)
+                                        .map(|(size_added, _)| size_added)
+                                        .collect();
+      self.target_size = if sizes_added.is_empty()
+      {
+         None
+      }
+      else
+      {
+         Some(formula.len() + sizes_added[rng.gen_range(0, sizes_added.len())])
+      };
+   }
+
+   /// picks a rule that can reach the size of the formula, a random rule if there is none
+   fn choose<RNG: Rng>(&mut self,
+                       formula: &Formula<State>,
+                       stack: &[State],
+                       rules: &[Vec<State>],
+                       rng: &mut RNG)
+                       -> usize
+   {
+      if let Some(size_added) = self.target_size.and_then(|size| size.checked_sub(formula.len()))
+      {
+         let weights = self.sampler.rule_weights(stack, rules, size_added);
+         if weights.iter().any(|&weight| weight > 0.)
+         {
+            return pick(&weights, rng);
+         }
+      }
+      rng.gen_range(0, rules.len())
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use rand::SeedableRng;
+   use rand_xoshiro::Xoshiro256Plus;
+   use super::super::rollout::rollout_expand;
+   use super::super::test_grammar::Sum;
+
+   /// a list of digits in base 100, there are 100^n formulas with n states
+   #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+   enum Digits
+   {
+      List,
+      Digit,
+      Value(u8)
+   }
+
+   impl Grammar for Digits
+   {
+      type ScoreType = usize;
+
+      fn root_state() -> Self
+      {
+         Digits::List
+      }
+
+      fn expand(self) -> Vec<Vec<Self>>
+      {
+         match self
+         {
+            Digits::List => vec![vec![Digits::Digit], vec![Digits::Digit, Digits::List]],
+            Digits::Digit => (0..100).map(|value| vec![Digits::Value(value)]).collect(),
+            Digits::Value(_) => vec![]
+         }
+      }
+
+      fn to_string(formula: &Formula<Self>) -> String
+      {
+         format!("{:?}", formula.iter().collect::<Vec<_>>())
+      }
+
+      fn evaluate(formula: &Formula<Self>) -> Self::ScoreType
+      {
+         formula.len()
+      }
+   }
+
+   /// a cycle of single state rules, the only formulas being [Leaf] and [Other]
+   #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+   enum Cycle
+   {
+      A,
+      B,
+      Leaf,
+      Other
+   }
+
+   impl Grammar for Cycle
+   {
+      type ScoreType = usize;
+
+      fn root_state() -> Self
+      {
+         Cycle::A
+      }
+
+      fn expand(self) -> Vec<Vec<Self>>
+      {
+         match self
+         {
+            Cycle::A => vec![vec![Cycle::B], vec![Cycle::Leaf]],
+            Cycle::B => vec![vec![Cycle::A], vec![Cycle::Other]],
+            _ => vec![]
+         }
+      }
+
+      fn to_string(formula: &Formula<Self>) -> String
+      {
+         format!("{:?}", formula.iter().collect::<Vec<_>>())
+      }
+
+      fn evaluate(formula: &Formula<Self>) -> Self::ScoreType
+      {
+         formula.len()
+      }
+   }
+
+   #[test]
+   fn counts_catalan_numbers()
+   {
+      let mut sampler = SizeSampler::<Sum>::new(11);
+      let counts: Vec<f64> = (1..=11).map(|size| sampler.nb_formulas(size)).collect();
+      assert_eq!(counts, [1., 0., 1., 0., 2., 0., 5., 0., 14., 0., 42.]);
+   }
+
+   #[test]
+   fn samples_uniformly_among_formulas_of_a_size()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut sampler = SizeSampler::<Sum>::new(9);
+      assert!(sampler.sample(8, &mut rng).is_none());
+      let nb_samples = 14_000;
+      let mut frequencies: HashMap<Formula<Sum>, usize> = HashMap::new();
+      for _ in 0..nb_samples
+      {
+         let formula = sampler.sample(9, &mut rng).unwrap();
+         assert_eq!(formula.len(), 9);
+         *frequencies.entry(formula).or_insert(0) += 1;
+      }
+      // 14 trees, each expected 1000 times
+      assert_eq!(frequencies.len(), 14);
+      assert!(frequencies.values().all(|&frequency| (frequency > 850) && (frequency < 1150)));
+   }
+
+   #[test]
+   fn boltzmann_favours_short_formulas()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut sampler = SizeSampler::<Sum>::new(21);
+      let mean_size = |sampler: &mut SizeSampler<Sum>, parameter: f64, rng: &mut Xoshiro256Plus| {
+         (0..1000).map(|_| sampler.sample_boltzmann(parameter, rng).unwrap().len()).sum::<usize>() / 1000
+      };
+      let short = mean_size(&mut sampler, 0.3, &mut rng);
+      let long = mean_size(&mut sampler, 1., &mut rng);
+      assert!(short < long);
+      assert!(long > 15);
+   }
+
+   #[test]
+   fn cuts_cycles_of_single_state_rules()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut sampler = SizeSampler::<Cycle>::new(3);
+      assert_eq!(sampler.nb_formulas(1), 2.);
+      assert_eq!(sampler.nb_formulas(2), 0.);
+      for _ in 0..100
+      {
+         let formula = sampler.sample(1, &mut rng).unwrap();
+         assert!((formula[0] == Cycle::Leaf) || (formula[0] == Cycle::Other));
+      }
+   }
+
+   #[test]
+   fn boltzmann_handles_overflowing_counts()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut sampler = SizeSampler::<Digits>::new(160);
+      assert!(sampler.nb_formulas(160).is_infinite());
+      // the weight of a size is 100^size * 0.001^size = 0.1^size, most formulas contain a single state
+      let nb_samples = 1000;
+      let nb_single = (0..nb_samples).map(|_| sampler.sample_boltzmann(0.001, &mut rng).unwrap())
+                                     .filter(|formula| formula.len() == 1)
+                                     .count();
+      assert!(nb_single > 850);
+   }
+
+   #[test]
+   fn uniform_rollouts_are_uniform_by_size()
+   {
+      let mut rng = Xoshiro256Plus::seed_from_u64(0);
+      let mut rollout = UniformRollout::<Sum>::new(9);
+      let nb_samples = 10_000;
+      let mut size_frequencies: HashMap<usize, usize> = HashMap::new();
+      let mut frequencies: HashMap<Formula<Sum>, usize> = HashMap::new();
+      for _ in 0..nb_samples
+      {
+         let (formula, _) =
+            rollout_expand(Formula::empty(), vec![Sum::root_state()], &mut rng, 100, &mut rollout, |_| ());
+         *size_frequencies.entry(formula.len()).or_insert(0) += 1;
+         if formula.len() == 9
+         {
+            *frequencies.entry(formula).or_insert(0) += 1;
+         }
+      }
+      // the sizes 1, 3, 5, 7 and 9 are equally likely, each expected 2000 times
+      assert_eq!(size_frequencies.len(), 5);
+      assert!(size_frequencies.values().all(|&frequency| (frequency > 1800) && (frequency < 2200)));
+      // the 14 trees with 9 states are equally likely, each expected about 143 times
+      assert_eq!(frequencies.len(), 14);
+      assert!(frequencies.values().all(|&frequency| (frequency > 90) && (frequency < 200)));
+   }
+}
@@ -0,0 +1,83 @@
+use crate::grammar::{Grammar, Formula};
+
+/// strings of bits, scored by their number of ones
+/// a formula is made of 8 bits, each of them being a decision
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Bits
+{
+   Remaining(u8), // the given number of bits are still to be written
+   Zero,
+   One
+}
+
+impl Grammar for Bits
+{
+   type ScoreType = f64;
+
+   fn root_state() -> Self
+   {
+      Bits::Remaining(8)
+   }
+
+   fn expand(self) -> Vec<Vec<Self>>
+   {
+      match self
+      {
+         Bits::Remaining(0) => vec![vec![]],
+         Bits::Remaining(n) =>
+         {
+            vec![vec![Bits::Zero, Bits::Remaining(n - 1)], vec![Bits::One, Bits::Remaining(n - 1)]]
+         }
+         _ => vec![]
+      }
+   }
+
+   fn to_string(formula: &Formula<Self>) -> String
+   {
+      format!("{:?}", formula.iter().collect::<Vec<_>>())
+   }
+
+   fn evaluate(formula: &Formula<Self>) -> Self::ScoreType
+   {
+      formula.iter().filter(|&&state| state == Bits::One).count() as f64
+   }
+}
+
+/// binary trees whose leafs are ones, scored by their number of states
+/// NOTE: the number of trees with n additions is the n-th catalan number
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sum
+{
+   Expr,
+   One,
+   Add
+}
+
+impl Grammar for Sum
+{
+   type ScoreType = f64;
+
+   fn root_state() -> Self
+   {
+      Sum::Expr
+   }
+
+   fn expand(self) -> Vec<Vec<Self>>
+   {
+      match self
+      {
+         Sum::Expr => vec![vec![Sum::One], vec![Sum::Add, Sum::Expr, Sum::Expr]],
+         _ => vec![]
+      }
+   }
+
+   fn to_string(formula: &Formula<Self>) -> String
+   {
+      format!("{:?}", formula.iter().collect::<Vec<_>>())
+   }
+
+   fn evaluate(formula: &Formula<Self>) -> Self::ScoreType
+   {
+      formula.len() as f64
+   }
+}
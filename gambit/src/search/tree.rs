@@ -19,8 +19,26 @@ pub enum Tree<Distr: Distribution>
 /// encapsulate a distribution and several children
 pub struct Node<Distr: Distribution>
 {
-   pub distribution: Distr, // the distribution of the reward coming from this node
-   pub children: Box<[Tree<Distr>]>  // the children of this node
+   pub distribution: Distr,      // the distribution of the reward coming from this node
+   pub children: Children<Distr> // the children of this node, one per rule
+}
+
+/// the children of a node, indexed by rule
+/// only the children that have been visited are stored, the other rules are either untried or deleted
+/// NOTE: this avoids allocating one leaf per rule for grammars with a large number of rules
+pub struct Children<Distr: Distribution>
+{
+   visited: Vec<(u32, Tree<Distr>)>, // visited children and their rule index, sorted by rule index
+   untried: Untried,                 // the rules that have never been visited
+   nb_rules: u32
+}
+
+/// bitset of the rules that have never been visited
+/// NOTE: stored inline for nodes with at most 64 rules, which avoids an allocation per node
+enum Untried
+{
+   Inline(u64),
+   Boxed(Box<[u64]>)
 }
 
 /// represents the action that should be done now that we have expanded the tree
@@ -71,22 +89,30 @@ impl<Distr: Distribution> Tree<Distr>
          _ => false
       }
    }
-   /// returns true if the node type has a distribution
-   fn has_distribution(&self) -> bool
-   {
-      match self
-      {
-         Tree::KnownLeaf(_) | Tree::Node(_) => true,
-         _ => false
-      }
-   }
-   /// selects the child with the maximum score
+   /// selects the child with the maximum score, returns its rule index
    /// leafs having an infinite score, they are taken in priority
-   pub fn best_child<RNG: Rng>(children: &[Tree<Distr>],
+   pub fn best_child<RNG: Rng>(children: &Children<Distr>,
                                distribution_father: &Distr,
-                               mut rng: &mut RNG,
+                               rng: &mut RNG,
                                available_depth: i64)
                                -> usize
+   {
+      let nb_children = children.nb_rules();
+      Tree::best_child_among(children, nb_children, |_| 0., distribution_father, rng, available_depth)
+   }
+
+   /// selects the child with the maximum score
+   /// among the nb_children (not deleted) children with the highest prior
+   /// returns its rule index
+   /// leafs having an infinite score, they are taken in priority
+   /// NOTE: children with the same prior are unlocked in rule order
+   pub fn best_child_among<RNG: Rng, Prior: Fn(usize) -> f64>(children: &Children<Distr>,
+                                                              nb_children: usize,
+                                                              prior: Prior,
+                                                              distribution_father: &Distr,
+                                                              mut rng: &mut RNG,
+                                                              available_depth: i64)
+                                                              -> usize
    {
       // we return the first child which, by convention, should be on the shortest path to a valid formula
       // TODO provide mecanism to avoid relying on a convention and always going for the first child
@@ -94,60 +120,222 @@ impl<Distr: Distribution> Tree<Distr>
       {
          return 0;
       }
-      // if there is a leaf, return on leaf at random
-      let leaf_index = children.iter()
-                               .enumerate()
-                               .filter(|&(_, child)| child.is_unknown_leaf())
-                               .max_by_key(|_| rng.gen::<usize>()) // choose one at random
-                               .map(|(i, _)| i);
+      // the children unlocked by the widening (None if they are all unlocked)
+      let unlocked = if nb_children < children.nb_rules()
+      {
+         let mut alive: Vec<usize> = children.alive().map(|(i, _)| i).collect();
+         if nb_children < alive.len()
+         {
+            alive.select_nth_unstable_by(nb_children, |&i, &j| {
+                    FloatOrd(prior(j)).cmp(&FloatOrd(prior(i))).then(i.cmp(&j))
+                 });
+            alive.truncate(nb_children);
+            Some(alive)
+         }
+         else
+         {
+            None
+         }
+      }
+      else
+      {
+         None
+      };
+      let is_unlocked = |i: usize| unlocked.as_ref().is_none_or(|unlocked| unlocked.contains(&i));
+      // if there is a leaf, return the leaf favoured by the father (ties being broken at random)
+      let leaf_index = children.alive()
+                               .filter(|&(i, child)| child.is_none() && is_unlocked(i))
+                               .map(|(i, _)| i)
+                               .max_by_key(|&i| {
+                                  (FloatOrd(distribution_father.score_untried(i, rng)), rng.gen::<usize>())
+                               });
       match leaf_index
       {
          Some(index) => index,
          None =>
          {
             // if there is a children, returns the children with the maximum score
-            children.iter()
-                    .enumerate()
-                    .filter(|&(_, child)| !child.is_deleted())
-                    .max_by_key(|(_, child)| {
-                       FloatOrd(child.distribution().score(distribution_father, &mut rng))
+            children.alive()
+                    .filter(|&(i, _)| is_unlocked(i))
+                    .filter_map(|(i, child)| child.map(|child| (i, child)))
+                    .max_by_key(|&(i, child)| {
+                       FloatOrd(distribution_father.score_child(child.distribution(), i, &mut rng))
                     })
                     .map(|(i, _)| i)
                     .expect("best_child: tried to find the best child in an empty array.")
          }
       }
    }
+}
 
-   /// prunes all child but one in order to reduce memory occupation
-   /// TODO: this function is a quick hack in order to (inv)validate the concept
-   pub fn prune(&mut self)
+impl<Distr: Distribution> Children<Distr>
+{
+   /// creates the children of a node with the given number of rules, all of them untried
+   pub fn new(nb_rules: usize) -> Self
    {
-      if let Tree::Node(box Node { children, .. }) = self
+      Children { visited: Vec::new(), untried: Untried::new(nb_rules), nb_rules: nb_rules as u32 }
+   }
+
+   /// returns the number of rules (including the deleted ones)
+   pub fn nb_rules(&self) -> usize
+   {
+      self.nb_rules as usize
+   }
+
+   /// returns true if the rule has never been visited
+   pub fn is_untried(&self, rule_index: usize) -> bool
+   {
+      (self.untried.words()[rule_index / 64] >> (rule_index % 64)) & 1 == 1
+   }
+
+   /// sets the bit associated with an untried rule
+   fn set_untried(&mut self, rule_index: usize, untried: bool)
+   {
+      let bit = 1 << (rule_index % 64);
+      let word = &mut self.untried.words_mut()[rule_index / 64];
+      if untried
+      {
+         *word |= bit;
+      }
+      else
+      {
+         *word &= !bit;
+      }
+   }
+
+   /// returns the number of rules that have never been visited
+   pub fn nb_untried(&self) -> usize
+   {
+      self.untried.words().iter().map(|word| word.count_ones() as usize).sum()
+   }
+
+   /// returns true if all the children have been deleted
+   pub fn is_exhausted(&self) -> bool
+   {
+      self.visited.is_empty() && self.untried.words().iter().all(|&word| word == 0)
+   }
+
+   /// returns the position of a rule in the visited children (see `Vec::binary_search`)
+   fn position(&self, rule_index: usize) -> std::result::Result<usize, usize>
+   {
+      self.visited.binary_search_by_key(&(rule_index as u32), |&(index, _)| index)
+   }
+
+   /// returns the child associated with a rule, None if the rule is untried or deleted
+   pub fn get(&self, rule_index: usize) -> Option<&Tree<Distr>>
+   {
+      self.position(rule_index).ok().map(|position| &self.visited[position].1)
+   }
+
+   /// returns the child associated with a rule, None if the rule is untried or deleted
+   pub fn get_mut(&mut self, rule_index: usize) -> Option<&mut Tree<Distr>>
+   {
+      match self.position(rule_index)
       {
-         let max_visit: Vec<usize> = children.iter()
-                                             .enumerate()
-                                             .filter(|(_, child)| child.has_distribution())
-                                             .map(|(i, _)| i)
-                                             .collect();
-         match max_visit.as_slice()
+         Ok(position) => Some(&mut self.visited[position].1),
+         Err(_) => None
+      }
+   }
+
+   /// replaces the child associated with a rule
+   /// a deleted tree removes the child and a leaf marks the rule as untried
+   /// returns the previous child, None if the rule was untried or deleted
+   pub fn replace(&mut self, rule_index: usize, tree: Tree<Distr>) -> Option<Tree<Distr>>
+   {
+      let position = self.position(rule_index);
+      self.set_untried(rule_index, tree.is_unknown_leaf());
+      match (position, tree)
+      {
+         (Ok(position), Tree::Deleted) | (Ok(position), Tree::Leaf) => Some(self.visited.remove(position).1),
+         (Ok(position), tree) => Some(std::mem::replace(&mut self.visited[position].1, tree)),
+         (Err(_), Tree::Deleted) | (Err(_), Tree::Leaf) => None,
+         (Err(position), tree) =>
+         {
+            self.visited.insert(position, (rule_index as u32, tree));
+            None
+         }
+      }
+   }
+
+   /// iterates on the visited children and their rule index
+   pub fn visited(&self) -> impl Iterator<Item = (usize, &Tree<Distr>)>
+   {
+      self.visited.iter().map(|(index, child)| (*index as usize, child))
+   }
+
+   /// iterates on the rules that have not been deleted, in order, with their child if they have been visited
+   pub fn alive(&self) -> impl Iterator<Item = (usize, Option<&Tree<Distr>>)>
+   {
+      let mut visited = self.visited.iter().peekable();
+      (0..self.nb_rules()).filter_map(move |rule_index| {
+                             match visited.peek()
+                             {
+                                Some((index, child)) if *index as usize == rule_index =>
+                                {
+                                   visited.next();
+                                   Some((rule_index, Some(child)))
+                                }
+                                _ if self.is_untried(rule_index) => Some((rule_index, None)),
+                                _ => None
+                             }
+                          })
+   }
+
+   /// returns the sizes, in bytes, of the two allocations storing the children (visited and untried)
+   /// NOTE: this excludes the memory used by the children themselves
+   ///       the untried rules of a node with at most 64 rules are stored inline, without allocation
+   pub fn storage_size(&self) -> (usize, usize)
+   {
+      let visited_size = self.visited.capacity() * std::mem::size_of::<(u32, Tree<Distr>)>();
+      let untried_size = match &self.untried
+      {
+         Untried::Inline(_) => 0,
+         Untried::Boxed(words) => words.len() * std::mem::size_of::<u64>()
+      };
+      (visited_size, untried_size)
+   }
+}
+
+impl Untried
+{
+   /// returns a bitset with one bit set per rule
+   fn new(nb_rules: usize) -> Self
+   {
+      let nb_words = nb_rules.div_ceil(64);
+      // the bits past the last rule are cleared
+      let last_word = if nb_rules.is_multiple_of(64) { std::u64::MAX } else { (1 << (nb_rules % 64)) - 1 };
+      match nb_words
+      {
+         0 => Untried::Inline(0),
+         1 => Untried::Inline(last_word),
+         _ =>
          {
-            [] => panic!("tried to prune a tree with zero children"),
-            [index] => children[*index].prune(),
-            _ =>
-            {
-               let best_index =
-                  *max_visit.iter().max_by_key(|&&i| children[i].distribution().nb_visit()).unwrap();
-               for index in max_visit
-               {
-                  if index != best_index
-                  {
-                     children[index] = Tree::Deleted;
-                  }
-               }
-            }
+            let mut words = vec![std::u64::MAX; nb_words];
+            words[nb_words - 1] = last_word;
+            Untried::Boxed(words.into_boxed_slice())
          }
       }
    }
+
+   /// returns the words of the bitset
+   fn words(&self) -> &[u64]
+   {
+      match self
+      {
+         Untried::Inline(word) => std::slice::from_ref(word),
+         Untried::Boxed(words) => words
+      }
+   }
+
+   /// returns the words of the bitset
+   fn words_mut(&mut self) -> &mut [u64]
+   {
+      match self
+      {
+         Untried::Inline(word) => std::slice::from_mut(word),
+         Untried::Boxed(words) => words
+      }
+   }
 }
 
 impl<Distr: Distribution> ReturnType<Tree<Distr>>
@@ -164,3 +352,79 @@ impl<Distr: Distribution> ReturnType<Tree<Distr>>
       }
    }
 }
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+   use crate::distribution::ThompsonMax;
+
+   /// returns a visited child
+   fn known_leaf() -> Tree<ThompsonMax>
+   {
+      Tree::KnownLeaf(Box::new(ThompsonMax::new()))
+   }
+
+   /// returns the rule indexes of the children that have not been deleted
+   fn alive_rules(children: &Children<ThompsonMax>) -> Vec<usize>
+   {
+      children.alive().map(|(rule_index, _)| rule_index).collect()
+   }
+
+   #[test]
+   fn replace_moves_rules_between_untried_visited_and_deleted()
+   {
+      let mut children = Children::<ThompsonMax>::new(3);
+      assert!(children.replace(1, known_leaf()).is_none());
+      assert!(!children.is_untried(1) && children.get(1).is_some());
+      assert_eq!(children.nb_untried(), 2);
+      assert!(matches!(children.replace(1, known_leaf()), Some(Tree::KnownLeaf(_))));
+      // a leaf marks the rule as untried again
+      assert!(children.replace(1, Tree::Leaf).is_some());
+      assert!(children.is_untried(1) && children.get(1).is_none());
+      assert_eq!(children.nb_untried(), 3);
+      // both untried and visited rules can be deleted
+      assert!(children.replace(0, Tree::Deleted).is_none());
+      children.replace(2, known_leaf());
+      assert!(children.replace(2, Tree::Deleted).is_some());
+      assert_eq!(alive_rules(&children), vec![1]);
+      assert!(!children.is_exhausted());
+      children.replace(1, Tree::Deleted);
+      assert!(alive_rules(&children).is_empty());
+      assert!(children.is_exhausted());
+   }
+
+   #[test]
+   fn alive_lists_the_visited_children_in_rule_order()
+   {
+      let mut children = Children::<ThompsonMax>::new(4);
+      children.replace(3, known_leaf());
+      children.replace(0, known_leaf());
+      children.replace(2, Tree::Deleted);
+      let alive: Vec<(usize, bool)> = children.alive().map(|(i, child)| (i, child.is_some())).collect();
+      assert_eq!(alive, vec![(0, true), (1, false), (3, true)]);
+      let visited: Vec<usize> = children.visited().map(|(i, _)| i).collect();
+      assert_eq!(visited, vec![0, 3]);
+   }
+
+   #[test]
+   fn untried_bitset_handles_word_boundaries()
+   {
+      for &nb_rules in &[1, 63, 64, 65, 128, 129]
+      {
+         let mut children = Children::<ThompsonMax>::new(nb_rules);
+         assert_eq!(children.nb_untried(), nb_rules);
+         assert_eq!(alive_rules(&children), (0..nb_rules).collect::<Vec<_>>());
+         // the bitset is only allocated past 64 rules
+         let (_, untried_size) = children.storage_size();
+         assert_eq!(untried_size == 0, nb_rules <= 64);
+         for rule_index in (0..nb_rules).rev()
+         {
+            assert!(!children.is_exhausted());
+            children.replace(rule_index, Tree::Deleted);
+         }
+         assert!(children.is_exhausted());
+         assert_eq!(children.nb_untried(), 0);
+      }
+   }
+}
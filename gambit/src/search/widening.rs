@@ -0,0 +1,51 @@
+/// limits the number of children of a node that can be explored given its number of visits
+/// so that nodes with a large number of rules can be exploited before all their children have been tried
+/// NOTE: the children are unlocked by decreasing prior (see `Grammar::rule_prior`),
+///       rules with the same prior are unlocked in the order of the rules
+pub trait Widening
+{
+   /// returns the number of children, among nb_rules, that can be explored after nb_visit visits
+   fn nb_children(&self, nb_visit: u64, nb_rules: usize) -> usize;
+}
+
+//-----------------------------------------------------------------------------
+// NO WIDENING
+
+/// all the children can be explored from the first visit
+pub struct NoWidening;
+
+impl Widening for NoWidening
+{
+   fn nb_children(&self, _nb_visit: u64, nb_rules: usize) -> usize
+   {
+      nb_rules
+   }
+}
+
+//-----------------------------------------------------------------------------
+// PROGRESSIVE WIDENING
+
+/// ceil(k * nb_visit^alpha) children can be explored after nb_visit visits (and at least one)
+/// NOTE: the number of visits is given by the distribution of the node (see `Distribution::nb_visit`)
+pub struct ProgressiveWidening
+{
+   pub k: f64,
+   pub alpha: f64 // in [0,1], the closer to 0 the slower the widening
+}
+
+impl Default for ProgressiveWidening
+{
+   fn default() -> Self
+   {
+      ProgressiveWidening { k: 1., alpha: 0.5 }
+   }
+}
+
+impl Widening for ProgressiveWidening
+{
+   fn nb_children(&self, nb_visit: u64, nb_rules: usize) -> usize
+   {
+      let nb_children = (self.k * (nb_visit as f64).powf(self.alpha)).ceil() as usize;
+      nb_children.max(1).min(nb_rules)
+   }
+}